@@ -56,7 +56,9 @@ fn kmain() -> ! {
     //     kprintln!("{:?}", v);
     // }
 
-    shell(">");
+    loop {
+        shell(">");
+    }
 }
 
 use pi::uart::MiniUart;
@@ -1,56 +1,198 @@
-use shim::io::Write;
+use shim::io::{Read, Seek, Write};
 use shim::io;
-use shim::path::{Path, PathBuf};
+use shim::path::{Component, Path, PathBuf};
 
 use stack_vec::StackVec;
 
-use pi::atags::Atags;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+use pi::atags::{Atag, Atags};
+use pi::{pm, timer};
+
+use xmodem::{Progress, Xmodem};
 
 use fat32::traits::FileSystem;
-use fat32::traits::{Dir, Entry};
+use fat32::traits::{Dir, Entry, File, Metadata, Timestamp};
 
-use crate::console::{kprint, kprintln, CONSOLE};
-use crate::ALLOCATOR;
+use crate::console::{kprint, kprintln, Console, CONSOLE};
 use crate::FILESYSTEM;
 
 use core::prelude::rust_2024::derive;
 
-use core::fmt::Debug;
+use core::fmt::{self, Debug};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::time::Duration;
 use core::iter::Iterator;
 use core::result::Result;
 use core::result::Result::{Err, Ok};
 
+#[cfg(test)]
+mod tests;
+
 /// Error type for `Command` parse failures.
 #[derive(Debug)]
 enum Error {
     Empty,
-    TooManyArgs,
+    /// The line held `count` arguments but only `capacity` fit.
+    TooManyArgs { capacity: usize, count: usize },
+    UnterminatedQuote,
+    /// A `>` or `>>` wasn't followed by exactly one file name.
+    BadRedirect,
+    /// The command's arguments no longer fit in their buffer once variables
+    /// were expanded.
+    TooLong,
+}
+
+/// Splits the first argument off of `s`, returning it as written, quotes
+/// included, along with the rest of the string, or `None` if `s` holds only
+/// whitespace. Arguments are separated by ASCII whitespace; double-quoted
+/// text, in which `\"` stands for a quote, is part of the surrounding argument.
+///
+/// # Errors
+///
+/// Returns `Error::UnterminatedQuote` if a quote is never closed.
+fn next_arg(s: &str) -> Result<Option<(&str, &str)>, Error> {
+    let s = s.trim_start_matches(|c: char| c.is_ascii_whitespace());
+    if s.is_empty() {
+        return Ok(None);
+    }
+
+    let bytes = s.as_bytes();
+    let (mut i, mut quoted) = (0, false);
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' if quoted && bytes.get(i + 1) == Some(&b'"') => i += 1,
+            b'"' => quoted = !quoted,
+            byte if !quoted && byte.is_ascii_whitespace() => break,
+            _ => {}
+        }
+        i += 1;
+    }
+
+    if quoted {
+        return Err(Error::UnterminatedQuote);
+    }
+
+    Ok(Some(s.split_at(i)))
+}
+
+/// Expands the argument `raw`, as split off by `next_arg`, into the front of
+/// `out` and returns it, leaving `out` with the space after it.
+///
+/// # Errors
+///
+/// Returns `Error::TooLong` if the expansion doesn't fit in `out`.
+fn take_arg<'a>(out: &mut &'a mut [u8], raw: &str, vars: &StackVec<Variable>) -> Result<&'a str, Error> {
+    let storage = core::mem::take(out);
+    let mut arg = StackVec::new(&mut *storage);
+    expand(raw, vars, &mut arg)?;
+    let len = arg.len();
+    let (arg, rest) = storage.split_at_mut(len);
+    *out = rest;
+    // `expand` copies whole characters, so the result is UTF-8.
+    core::str::from_utf8(arg).map_err(|_| Error::TooLong)
+}
+
+/// The file that a command's output is redirected to with `>` (truncating
+/// it) or `>>` (appending to it).
+#[derive(Debug, PartialEq)]
+struct Redirect<'a> {
+    path: &'a str,
+    append: bool,
+}
+
+/// Splits the first command of the sequence `s` off at the first `;` that
+/// isn't inside double quotes, returning it along with the rest of the
+/// sequence, or `None` as the rest if `s` holds a single command.
+fn next_command(s: &mut str) -> (&mut str, Option<&mut str>) {
+    let bytes = s.as_bytes();
+    let (mut i, mut quoted) = (0, false);
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' if quoted && bytes.get(i + 1) == Some(&b'"') => i += 1,
+            b'"' => quoted = !quoted,
+            b';' if !quoted => {
+                let (command, rest) = s.split_at_mut(i);
+                return (command, Some(&mut rest[1..]));
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    (s, None)
 }
 
 /// A structure representing a single shell command.
 struct Command<'a> {
     args: StackVec<'a, &'a str>,
+    redirect: Option<Redirect<'a>>,
 }
 
 impl<'a> Command<'a> {
     /// Parse a command from a string `s` using `buf` as storage for the
-    /// arguments.
+    /// arguments and `out` for their text. Each argument is copied into `out`
+    /// by `expand`, which removes its quotes and expands the variables in
+    /// `vars`. An argument without quotes that expands to nothing is dropped.
     ///
     /// # Errors
     ///
     /// If `s` contains no arguments, returns `Error::Empty`. If there are more
-    /// arguments than `buf` can hold, returns `Error::TooManyArgs`.
-    fn parse(s: &'a str, buf: &'a mut [&'a str]) -> Result<Command<'a>, Error> {
+    /// arguments than `buf` can hold, returns `Error::TooManyArgs` with the
+    /// capacity of `buf` and the number of arguments on the line. If a quote
+    /// isn't closed, returns `Error::UnterminatedQuote`. If the expanded
+    /// arguments don't fit in `out`, returns `Error::TooLong`.
+    ///
+    /// An unquoted `>` or `>>` argument redirects the command's output to the
+    /// file named by the argument after it, which must be the last one. If
+    /// there is no such file name, or there are further arguments, returns
+    /// `Error::BadRedirect`.
+    fn parse(
+        s: &'a str,
+        vars: &StackVec<Variable>,
+        out: &'a mut [u8],
+        buf: &'a mut [&'a str],
+    ) -> Result<Command<'a>, Error> {
         let mut args = StackVec::new(buf);
-        for arg in s.split(' ').filter(|a| !a.is_empty()) {
-            args.push(arg).map_err(|_| Error::TooManyArgs)?;
+        let mut redirect = None;
+        let (mut rest, mut out) = (s, out);
+        while let Some((raw, tail)) = next_arg(rest)? {
+            // A `>` that's quoted, or comes from a variable, is an argument.
+            if raw == ">" || raw == ">>" {
+                let (path, tail) = next_arg(tail)?.ok_or(Error::BadRedirect)?;
+                if next_arg(tail)?.is_some() {
+                    return Err(Error::BadRedirect);
+                }
+
+                let path = take_arg(&mut out, path, vars)?;
+                redirect = Some(Redirect { path, append: raw == ">>" });
+                break;
+            }
+
+            rest = tail;
+            let arg = take_arg(&mut out, raw, vars)?;
+            // An unset variable on its own leaves no argument behind.
+            if arg.is_empty() && !raw.contains('"') {
+                continue;
+            }
+
+            if args.push(arg).is_err() {
+                let capacity = args.capacity();
+                let mut count = capacity + 1;
+                while let Ok(Some((_, tail))) = next_arg(rest) {
+                    count += 1;
+                    rest = tail;
+                }
+                return Err(Error::TooManyArgs { capacity, count });
+            }
         }
 
         if args.is_empty() {
             return Err(Error::Empty);
         }
 
-        Ok(Command { args })
+        Ok(Command { args, redirect })
     }
 
     /// Returns this command's path. This is equivalent to the first argument.
@@ -59,73 +201,2149 @@ impl<'a> Command<'a> {
     }
 }
 
-/// Starts a shell using `prefix` as the prefix for each line. This function
-/// returns if the `exit` command is called.
-use core::str::from_utf8;
-const MAX_LINE_LENGTH: usize = 512;
-pub fn shell(prefix: &str) -> ! {
-    kprintln!("{}", WELCOME_TXT);
+/// Returns `path` resolved against the working directory `cwd`, with any `.`
+/// and `..` components removed. `..` at the root refers to the root itself.
+fn resolve<P: AsRef<Path>>(cwd: &Path, path: P) -> PathBuf {
+    let mut resolved = PathBuf::from("/");
+    for component in cwd.join(path).components() {
+        match component {
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            Component::Normal(name) => resolved.push(name),
+            _ => {}
+        }
+    }
 
-    let mut console = CONSOLE.lock();
-    loop {
-        kprint!("{} ", prefix);
-        let mut storage = [0; MAX_LINE_LENGTH]; // maxiumum command size
-        let mut line: StackVec<u8> = StackVec::new(&mut storage);
-        let mut idx = 0;
+    resolved
+}
+
+/// Prints the working directory `cwd`.
+fn pwd(cwd: &Path) {
+    kprintln!("{}", cwd.display());
+}
+
+/// Opens the entry at `path`, resolved against `cwd`. Prints an error prefixed
+/// with `name` and returns `None` if the entry can't be opened.
+fn open(name: &str, cwd: &Path, path: &str) -> Option<impl Entry> {
+    match FILESYSTEM.open(resolve(cwd, path)) {
+        Ok(entry) => Some(entry),
+        Err(e) => {
+            kprintln!("{}: {}: {}", name, path, e);
+            None
+        }
+    }
+}
+
+/// Changes the working directory `cwd` to `args[0]`, or to `/` if no path is
+/// given. The working directory is left untouched if the path doesn't name a
+/// directory.
+fn cd(args: &[&str], cwd: &mut PathBuf) {
+    let path = match args {
+        [] => "/",
+        [path] => *path,
+        _ => {
+            kprintln!("cd: too many arguments");
+            return;
+        }
+    };
+
+    match open("cd", cwd, path) {
+        Some(ref entry) if entry.is_dir() => *cwd = resolve(cwd, path),
+        Some(_) => kprintln!("cd: {}: not a directory", path),
+        None => {}
+    }
+}
+
+/// Lists the entries of the directory `args[0]`, or of `cwd` if no path is
+/// given. Directories are printed with a trailing `/`. Hidden entries and
+/// dotfiles are skipped unless `-a` is passed first.
+fn ls(args: &[&str], cwd: &Path) {
+    let (all, args) = match args {
+        ["-a", rest @ ..] => (true, rest),
+        _ => (false, args),
+    };
+
+    let path = match args {
+        [] => ".",
+        [path] => *path,
+        _ => {
+            kprintln!("ls: too many arguments");
+            return;
+        }
+    };
+
+    let dir = match open("ls", cwd, path).map(|entry| entry.into_dir()) {
+        Some(Some(dir)) => dir,
+        Some(None) => return kprintln!("ls: {}: not a directory", path),
+        None => return,
+    };
+
+    let entries = match dir.entries() {
+        Ok(entries) => entries,
+        Err(e) => return kprintln!("ls: {}: {}", path, e),
+    };
+
+    for entry in entries {
+        if !all && (entry.metadata().hidden() || entry.name().starts_with('.')) {
+            continue;
+        }
+
+        if entry.is_dir() {
+            kprintln!("{}/", entry.name());
+        } else {
+            kprintln!("{}", entry.name());
+        }
+    }
+}
+
+/// A timestamp displayed in ISO 8601 form, as in `2019-03-14 15:09:26`.
+struct IsoTime<T>(T);
+
+impl<T: Timestamp> fmt::Display for IsoTime<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let t = &self.0;
+        write!(f, "{:04}-{:02}-{:02} ", t.year(), t.month(), t.day())?;
+        write!(f, "{:02}:{:02}:{:02}", t.hour(), t.minute(), t.second())
+    }
+}
+
+/// The report printed by `stat` for an entry with the metadata `metadata`.
+/// For files `size` is the size in bytes; it's `None` for directories.
+struct Stat<'a, M> {
+    size: Option<u64>,
+    metadata: &'a M,
+}
+
+impl<M: Metadata> fmt::Display for Stat<'_, M> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.size {
+            Some(size) => writeln!(f, "type      file\nsize      {} bytes", size)?,
+            None => writeln!(f, "type      directory")?,
+        }
+
+        let (read_only, hidden) = (self.metadata.read_only(), self.metadata.hidden());
+        write!(f, "flags     ")?;
+        match (read_only, hidden) {
+            (false, false) => writeln!(f, "none")?,
+            (true, false) => writeln!(f, "read-only")?,
+            (false, true) => writeln!(f, "hidden")?,
+            (true, true) => writeln!(f, "read-only, hidden")?,
+        }
+
+        writeln!(f, "created   {}", IsoTime(self.metadata.created()))?;
+        writeln!(f, "modified  {}", IsoTime(self.metadata.modified()))?;
+        write!(f, "accessed  {}", IsoTime(self.metadata.accessed()))
+    }
+}
+
+/// Returns the path operand of `stat`.
+fn stat_path<'a>(args: &[&'a str]) -> Result<&'a str, &'static str> {
+    match args {
+        [path] => Ok(path),
+        [] => Err("missing operand"),
+        _ => Err("too many arguments"),
+    }
+}
+
+/// Prints the type, size, flags and timestamps of the entry `args[0]`.
+fn stat(args: &[&str], cwd: &Path) {
+    let path = match stat_path(args) {
+        Ok(path) => path,
+        Err(e) => return kprintln!("stat: {}", e),
+    };
+
+    if let Some(entry) = open("stat", cwd, path) {
+        let size = entry.as_file().map(|file| file.size());
+        kprintln!("{}", Stat { size, metadata: entry.metadata() });
+    }
+}
+
+/// The kind of entry `find -type` selects.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum EntryKind {
+    File,
+    Dir,
+}
+
+/// The operands of `find`.
+#[derive(Debug, PartialEq)]
+struct FindArgs<'a> {
+    start: &'a str,
+    name: &'a str,
+    kind: Option<EntryKind>,
+}
+
+/// Parses the arguments of `find`: a starting directory and a name, with an
+/// optional `-type f` or `-type d` before, between or after them.
+fn find_args<'a>(args: &[&'a str]) -> Result<FindArgs<'a>, &'static str> {
+    let mut operands = [""; 2];
+    let (mut count, mut kind) = (0, None);
+    let mut args = args.iter();
+    while let Some(&arg) = args.next() {
+        if arg == "-type" {
+            kind = match args.next() {
+                Some(&"f") => Some(EntryKind::File),
+                Some(&"d") => Some(EntryKind::Dir),
+                _ => return Err("-type expects f or d"),
+            };
+        } else if count == operands.len() {
+            return Err("too many arguments");
+        } else {
+            operands[count] = arg;
+            count += 1;
+        }
+    }
+
+    match count {
+        2 => Ok(FindArgs { start: operands[0], name: operands[1], kind }),
+        _ => Err("usage: find DIR NAME [-type f|d]"),
+    }
+}
+
+/// Number of directories `find` can have waiting to be searched.
+const FIND_STACK_CAPACITY: usize = 64;
+
+/// Prints the path of every entry below a directory whose name matches,
+/// ignoring case as FAT32 does: `find DIR NAME [-type f|d]`. The tree is
+/// walked depth-first with an explicit stack rather than by recursion, to keep
+/// the kernel stack small; FAT32 has no links, so the walk can't cycle once `.`
+/// and `..` are skipped.
+fn find(args: &[&str], cwd: &Path) {
+    let args = match find_args(args) {
+        Ok(args) => args,
+        Err(e) => return kprintln!("find: {}", e),
+    };
+
+    match open("find", cwd, args.start) {
+        Some(ref entry) if entry.is_dir() => {}
+        Some(_) => return kprintln!("find: {}: not a directory", args.start),
+        None => return,
+    }
+
+    let mut storage: [PathBuf; FIND_STACK_CAPACITY] = core::array::from_fn(|_| PathBuf::new());
+    let mut pending = StackVec::new(&mut storage);
+    let _ = pending.push(resolve(cwd, args.start));
+
+    while let Some(dir) = pending.pop() {
+        let entries = match FILESYSTEM.open_dir(&dir).and_then(|d| d.entries()) {
+            Ok(entries) => entries,
+            Err(e) => {
+                kprintln!("find: {}: {}", dir.display(), e);
+                continue;
+            }
+        };
+
+        for entry in entries {
+            let name = entry.name();
+            if name == "." || name == ".." {
+                continue;
+            }
+
+            let path = dir.join(name);
+            let kind = if entry.is_dir() { EntryKind::Dir } else { EntryKind::File };
+            if name.eq_ignore_ascii_case(args.name) && (args.kind.is_none() || args.kind == Some(kind)) {
+                kprintln!("{}", path.display());
+            }
+
+            if kind == EntryKind::Dir {
+                if let Err(path) = pending.push(path) {
+                    kprintln!("find: {}: too many directories pending, skipped", path.display());
+                }
+            }
+        }
+    }
+}
+
+/// Number of directories `du` can have waiting to be sized.
+const DU_STACK_CAPACITY: usize = 64;
+
+/// Returns the total size of the files below the directory `start`, calling
+/// `report` with the path and size of each of its immediate subdirectories.
+///
+/// `list` calls its callback with the name of each entry of a directory other
+/// than `.` and `..`, along with its size if it's a file or `None` if it's a
+/// directory. The tree is walked depth-first with an explicit stack; a
+/// directory that doesn't fit on it is skipped with a warning, and the total
+/// leaves it out.
+fn disk_usage<L>(start: &Path, list: &mut L, report: &mut dyn FnMut(&Path, u64)) -> io::Result<u64>
+where
+    L: FnMut(&Path, &mut dyn FnMut(&str, Option<u64>)) -> io::Result<()>,
+{
+    // Each path is paired with whether it's an immediate subdirectory of
+    // `start`.
+    let mut storage: [(PathBuf, bool); DU_STACK_CAPACITY] = core::array::from_fn(|_| (PathBuf::new(), false));
+    walk_usage(&mut StackVec::new(&mut storage), start, list, report)
+}
+
+/// Does the walk of `disk_usage` using `pending` as the stack of directories
+/// left to size.
+fn walk_usage<L>(
+    pending: &mut StackVec<(PathBuf, bool)>,
+    start: &Path,
+    list: &mut L,
+    report: &mut dyn FnMut(&Path, u64),
+) -> io::Result<u64>
+where
+    L: FnMut(&Path, &mut dyn FnMut(&str, Option<u64>)) -> io::Result<()>,
+{
+    // Lists `dir`, adding the sizes of its files to `total` and pushing its
+    // subdirectories onto `pending`.
+    let mut visit = |pending: &mut StackVec<(PathBuf, bool)>, dir: &Path, top: bool, total: &mut u64| {
+        list(dir, &mut |name, size| match size {
+            Some(size) => *total += size,
+            None => {
+                if let Err((path, _)) = pending.push((dir.join(name), top)) {
+                    kprintln!("du: {}: too many directories pending, skipped", path.display());
+                }
+            }
+        })
+    };
+
+    let mut total = 0;
+    visit(pending, start, true, &mut total)?;
+
+    // A subdirectory's descendants are all pushed above the subdirectories of
+    // `start` still pending, so each is fully sized before the next is popped.
+    let mut child: Option<(PathBuf, u64)> = None;
+    while let Some((dir, top)) = pending.pop() {
+        if top {
+            if let Some((path, size)) = child.replace((dir.clone(), total)) {
+                report(&path, total - size);
+            }
+        }
+
+        visit(pending, &dir, false, &mut total)?;
+    }
+
+    if let Some((path, size)) = child {
+        report(&path, total - size);
+    }
+
+    Ok(total)
+}
+
+/// Prints the total size of the files below a directory, after the size of
+/// each of its subdirectories: `du [-b] PATH`. Sizes are in KiB, rounded up,
+/// or in bytes with `-b`. For a file, just its size is printed.
+fn du(args: &[&str], cwd: &Path) {
+    let (bytes, args) = match args {
+        ["-b", rest @ ..] => (true, rest),
+        _ => (false, args),
+    };
+
+    let path = match args {
+        [] => ".",
+        [path] => *path,
+        _ => return kprintln!("du: too many arguments"),
+    };
+
+    let print = |path: &Path, size: u64| match bytes {
+        true => kprintln!("{}\t{}", size, path.display()),
+        false => kprintln!("{}\t{}", size.div_ceil(1024), path.display()),
+    };
+
+    let start = resolve(cwd, path);
+    let entry = match open("du", cwd, path) {
+        Some(entry) => entry,
+        None => return,
+    };
+
+    if let Some(file) = entry.as_file() {
+        return print(&start, file.size());
+    }
+
+    let mut list = |dir: &Path, f: &mut dyn FnMut(&str, Option<u64>)| -> io::Result<()> {
+        for entry in FILESYSTEM.open_dir(dir)?.entries()? {
+            let name = entry.name();
+            if name != "." && name != ".." {
+                f(name, entry.as_file().map(|file| file.size()));
+            }
+        }
+
+        Ok(())
+    };
+
+    match disk_usage(&start, &mut list, &mut |path, size| print(path, size)) {
+        Ok(total) => print(&start, total),
+        Err(e) => kprintln!("du: {}: {}", path, e),
+    }
+}
 
-        // get bytes
+/// Size of the buffer used to stream file contents to the console.
+const CAT_CHUNK_SIZE: usize = 256;
+
+/// Writes the contents of each file in `args` to the console, byte for byte.
+fn cat(args: &[&str], cwd: &Path) {
+    if args.is_empty() {
+        kprintln!("cat: missing file operand");
+        return;
+    }
+
+    for path in args {
+        let mut file = match open("cat", cwd, path).map(|entry| entry.into_file()) {
+            Some(Some(file)) => file,
+            Some(None) => {
+                kprintln!("cat: {}: is a directory", path);
+                continue;
+            }
+            None => continue,
+        };
+
+        let mut chunk = [0u8; CAT_CHUNK_SIZE];
         loop {
-            match console.read_byte() {
-                b'\r' | b'\n' => break,
-                8 | 127 => {
-                    if idx != 0 {
-                        console.write_byte(8u8);
-                        console.write_byte(b' ');
-                        console.write_byte(8u8);
-                        idx -= 1;
-                        line.pop();
-                    }
+            match file.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(n) => {
+                    let mut console = CONSOLE.lock();
+                    chunk[..n].iter().for_each(|&byte| console.write_byte(byte));
                 }
-                byte if (byte as char).is_ascii() && idx < MAX_LINE_LENGTH => match line.push(byte) {
-                    Ok(()) => {
-                        kprint!("{}", byte as char);
-                        idx += 1;
-                    }
-                    Err(()) => {
-                        console
-                            .write("failed".as_bytes())
-                            .expect("failed to write to console");
-                    }
-                },
-                _ => {
-                    console.write_byte(7u8); // rings the bell
-                }
-            }
-        }
-        kprintln!("");
-        match from_utf8(line.into_slice()){ 
-            Ok(command_string) if command_string.len() != 0 => {
-                let mut buf = [""; 64];
-                match Command::parse(command_string, &mut buf) {
-                    Ok(command) if command.path() == "echo" => {
-                        command.args.iter().skip(1).for_each(|s| kprint!("{} ", *s));
-                        kprintln!("");
-                    },
-                    Ok(command) if command.path() == "welcome"=> {
-                        kprintln!("{}", WELCOME_TXT);
-                    },
-                    Ok(command) => {
-                        kprintln!("unknown command: {}", command.path());
-                    },
-                    Err(Error::TooManyArgs)  => {
-                        kprintln!("error: too many arguments");
-                    },
+                Err(e) => {
+                    kprintln!("cat: {}: {}", path, e);
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Prints the ATAGs passed to the kernel by the firmware.
+fn atags() {
+    let mut found = false;
+    for atag in Atags::get() {
+        found = true;
+        match atag {
+            Atag::Core(core) => kprintln!(
+                "CORE  flags {:#x}, page size {:#x}, root device {:#x}",
+                core.flags,
+                core.page_size,
+                core.root_dev
+            ),
+            Atag::Mem(mem) => kprintln!(
+                "MEM   start {:#010x}, size {:#010x} ({} KiB)",
+                mem.start,
+                mem.size,
+                mem.size / 1024
+            ),
+            Atag::Cmd(cmd) => kprintln!("CMD   {}", cmd),
+            Atag::Unknown(id) => kprintln!("tag   {:#010x}", id),
+            Atag::None => {}
+        }
+    }
+
+    if !found {
+        kprintln!("atags: no ATAGs found");
+    }
+}
+
+/// Clears the terminal and moves the cursor to the top-left corner.
+fn clear() {
+    kprint!("\x1b[2J\x1b[H");
+}
+
+/// The output of `echo`: the arguments separated by spaces, followed by a
+/// newline unless `-n` is given. With `-e`, the escapes `\n`, `\t` and `\\`
+/// in the arguments are interpreted. Flags, which may be combined as in
+/// `-ne`, are only recognized in the first argument.
+struct Echo<'a> {
+    args: &'a [&'a str],
+    newline: bool,
+    escapes: bool,
+}
+
+impl<'a> Echo<'a> {
+    fn new(args: &'a [&'a str]) -> Echo<'a> {
+        let mut echo = Echo { args, newline: true, escapes: false };
+        if let Some(flags) = args.first().and_then(|arg| arg.strip_prefix('-')) {
+            if !flags.is_empty() && flags.chars().all(|c| c == 'n' || c == 'e') {
+                echo.newline = !flags.contains('n');
+                echo.escapes = flags.contains('e');
+                echo.args = &args[1..];
+            }
+        }
+
+        echo
+    }
+}
+
+impl fmt::Display for Echo<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, arg) in self.args.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+
+            if !self.escapes {
+                write!(f, "{}", arg)?;
+                continue;
+            }
+
+            let mut chars = arg.chars();
+            while let Some(c) = chars.next() {
+                match (c, chars.clone().next()) {
+                    ('\\', Some('n')) => f.write_str("\n")?,
+                    ('\\', Some('t')) => f.write_str("\t")?,
+                    ('\\', Some('\\')) => f.write_str("\\")?,
                     _ => {
-                        kprintln!("error: failed to parse");
+                        write!(f, "{}", c)?;
+                        continue;
                     }
                 }
-            }, 
-            _ => {}
+                chars.next();
+            }
+        }
+
+        if self.newline {
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Parses the arguments of `sleep`: a single number of milliseconds.
+fn sleep_duration(args: &[&str]) -> Result<Duration, &'static str> {
+    match args {
+        [] => Err("missing duration"),
+        [ms] => ms
+            .parse()
+            .map(Duration::from_millis)
+            .map_err(|_| "duration must be a number of milliseconds"),
+        _ => Err("too many arguments"),
+    }
+}
+
+/// Pauses for the number of milliseconds in `args`. The shell doesn't respond
+/// to input until the time has passed, so very large values block it for
+/// just as long.
+fn sleep(args: &[&str]) {
+    match sleep_duration(args) {
+        Ok(duration) => timer::spin_sleep(duration),
+        Err(e) => kprintln!("sleep: {}", e),
+    }
+}
+
+/// Maximum length in bytes of a prompt set with the `prompt` command.
+const PROMPT_CAPACITY: usize = 64;
+
+/// Writes the shell's prompt to `out`: the custom prompt `custom` with each
+/// `{cwd}` replaced by the working directory `cwd`, or, if `custom` is empty,
+/// `prefix` followed by a space.
+fn write_prompt<W: fmt::Write>(out: &mut W, prefix: &str, custom: &str, cwd: &Path) -> fmt::Result {
+    if custom.is_empty() {
+        return write!(out, "{} ", prefix);
+    }
+
+    let mut parts = custom.split("{cwd}");
+    out.write_str(parts.next().unwrap_or(""))?;
+    for part in parts {
+        write!(out, "{}", cwd.display())?;
+        out.write_str(part)?;
+    }
+
+    Ok(())
+}
+
+/// Sets the custom prompt `custom` to `args[0]`, or clears it to go back to
+/// the default prompt if no argument is given.
+fn prompt(args: &[&str], custom: &mut StackVec<u8>) {
+    match args {
+        [] => custom.clear(),
+        [prompt] if prompt.len() <= custom.capacity() => {
+            custom.clear();
+            let _ = custom.extend_from_slice(prompt.as_bytes());
+        }
+        [_] => kprintln!("prompt: prompt is longer than {} bytes", custom.capacity()),
+        _ => kprintln!("prompt: too many arguments"),
+    }
+}
+
+/// Time elapsed since boot, displayed as days, hours, minutes and seconds,
+/// omitting leading units that are zero, as in `1h 0m 5s`.
+struct Uptime(Duration);
+
+impl fmt::Display for Uptime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let secs = self.0.as_secs();
+        let (days, hours, minutes) = (secs / 86400, secs / 3600 % 24, secs / 60 % 60);
+        if days > 0 {
+            write!(f, "{}d ", days)?;
+        }
+        if days > 0 || hours > 0 {
+            write!(f, "{}h ", hours)?;
+        }
+        if secs >= 60 {
+            write!(f, "{}m ", minutes)?;
+        }
+
+        write!(f, "{}s", secs % 60)
+    }
+}
+
+/// Prints the time elapsed since boot, as measured by the system timer.
+fn uptime() {
+    kprintln!("up {}", Uptime(timer::current_time()));
+}
+
+/// A time of day, or a duration, displayed as `HH:MM:SS`. Hours aren't
+/// wrapped at 24, so a duration of more than a day shows all of its hours.
+struct Clock(Duration);
+
+impl fmt::Display for Clock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let secs = self.0.as_secs();
+        write!(f, "{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    }
+}
+
+/// Prints the current time. The Pi has no real-time clock, so there is no
+/// wall-clock time to print; the time since boot is printed instead, as
+/// measured by the system timer and labeled as uptime.
+fn date() {
+    kprintln!("uptime {} (no real-time clock)", Clock(timer::current_time()));
+}
+
+/// Time given to the watchdog to reset the board before `reboot` gives up.
+const REBOOT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Resets the board with the watchdog after flushing the console. Returns with
+/// an error message if the board is still running after `REBOOT_TIMEOUT`.
+fn reboot() {
+    kprintln!("rebooting...");
+    let _ = CONSOLE.lock().flush();
+    pm::reset();
+    timer::spin_sleep(REBOOT_TIMEOUT);
+    kprintln!("reboot: the watchdog didn't reset the board");
+}
+
+/// The built-in commands and a one-line description of each, as listed by
+/// `help`.
+const COMMANDS: &[(&str, &str)] = &[
+    ("atags", "print the ATAGs passed by the firmware"),
+    ("cat", "print the contents of files"),
+    ("cd", "change the working directory"),
+    ("clear", "clear the screen"),
+    ("date", "print the time since boot, as there's no real-time clock"),
+    ("du", "print the disk usage of a directory tree"),
+    ("echo", "print the arguments"),
+    ("env", "list the shell variables"),
+    ("exit", "exit the shell"),
+    ("find", "search a directory tree for entries by name"),
+    ("grep", "print the lines of a file containing a pattern"),
+    ("help", "list the available commands"),
+    ("hexdump", "print the contents of a file in hex"),
+    ("history", "list recent commands; !N runs command N"),
+    ("less", "show a file a page at a time"),
+    ("ls", "list the entries of a directory"),
+    ("mkdir", "create directories"),
+    ("prompt", "set the prompt; {cwd} shows the working directory"),
+    ("pwd", "print the working directory"),
+    ("reboot", "reset the board"),
+    ("rm", "remove files and empty directories"),
+    ("set", "set a shell variable, used as $NAME"),
+    ("sleep", "pause for a number of milliseconds"),
+    ("stat", "print the metadata of an entry"),
+    ("touch", "create empty files"),
+    ("unset", "remove shell variables"),
+    ("uptime", "print the time since boot"),
+    ("wc", "count the lines, words and bytes in a file"),
+    ("welcome", "print the welcome message"),
+    ("which", "tell whether names are built-in commands"),
+    ("xmodem", "send or receive a file over the console"),
+];
+
+/// Prints each built-in command with its description.
+fn help() {
+    for (name, description) in COMMANDS {
+        kprintln!("  {:<10}{}", name, description);
+    }
+}
+
+/// Returns the description of the built-in command `name`, if there is one.
+fn describe(name: &str) -> Option<&'static str> {
+    COMMANDS.iter().find(|(command, _)| *command == name).map(|(_, description)| *description)
+}
+
+/// Prints whether each name in `args` is a built-in command, along with the
+/// description `help` gives it.
+fn which(args: &[&str]) {
+    if args.is_empty() {
+        return kprintln!("which: missing command name");
+    }
+
+    for name in args {
+        match describe(name) {
+            Some(description) => kprintln!("{}: shell built-in: {}", name, description),
+            None => kprintln!("which: {}: not found", name),
+        }
+    }
+}
+
+/// Prints the error `e` that `name` hit while modifying `path`. The filesystem
+/// reports modifications of a read-only volume as `PermissionDenied`.
+fn print_write_error(name: &str, path: &str, e: io::Error) {
+    match e.kind() {
+        io::ErrorKind::PermissionDenied => kprintln!("{}: {}: read-only filesystem", name, path),
+        _ => kprintln!("{}: {}: {}", name, path, e),
+    }
+}
+
+/// Creates each directory in `args`. Their parent directories must exist.
+fn mkdir(args: &[&str], cwd: &Path) {
+    if args.is_empty() {
+        return kprintln!("mkdir: missing operand");
+    }
+
+    for path in args {
+        if let Err(e) = FILESYSTEM.create_dir(resolve(cwd, path), false) {
+            print_write_error("mkdir", path, e);
+        }
+    }
+}
+
+/// Creates each file in `args` that doesn't exist yet as an empty file.
+fn touch(args: &[&str], cwd: &Path) {
+    if args.is_empty() {
+        return kprintln!("touch: missing file operand");
+    }
+
+    for path in args {
+        let path_buf = resolve(cwd, path);
+        if FILESYSTEM.open(&path_buf).is_ok() {
+            continue;
+        }
+
+        if let Err(e) = FILESYSTEM.create_file(path_buf) {
+            print_write_error("touch", path, e);
+        }
+    }
+}
+
+/// Removes each file or empty directory in `args`.
+fn rm(args: &[&str], cwd: &Path) {
+    if args.is_empty() {
+        return kprintln!("rm: missing operand");
+    }
+
+    for path in args {
+        if let Err(e) = FILESYSTEM.remove(resolve(cwd, path), false) {
+            print_write_error("rm", path, e);
+        }
+    }
+}
+
+/// Writes `output` of the command `name` to the file it's redirected to by
+/// `redirect`, resolved against `cwd`. The file is created if it doesn't
+/// exist, and its previous contents are replaced unless `redirect` appends.
+fn write_redirected(name: &str, redirect: &Redirect, cwd: &Path, output: fmt::Arguments) {
+    let result = open_for_writing(name, cwd, redirect.path, redirect.append).map(|file| {
+        file.and_then(|mut file| {
+            file.write_fmt(output)?;
+            file.sync()
+        })
+    });
+
+    if let Some(Err(e)) = result {
+        print_write_error(name, redirect.path, e);
+    }
+}
+
+/// Opens the file at `path`, resolved against `cwd`, for writing, creating it
+/// if it doesn't exist. Its previous contents are replaced unless `append` is
+/// set, in which case writes go to its end. Prints an error prefixed with
+/// `name` and returns `None` if the entry can't be opened.
+fn open_for_writing(name: &str, cwd: &Path, path: &str, append: bool) -> Option<io::Result<impl File>> {
+    let resolved = resolve(cwd, path);
+    let existing = match FILESYSTEM.open(&resolved) {
+        Ok(entry) => match entry.into_file() {
+            Some(file) => Some(file),
+            None => {
+                kprintln!("{}: {}: is a directory", name, path);
+                return None;
+            }
+        },
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => {
+            kprintln!("{}: {}: {}", name, path, e);
+            return None;
+        }
+    };
+
+    Some(match existing {
+        Some(mut file) if append => file.seek(io::SeekFrom::End(0)).map(|_| file),
+        Some(_) => FILESYSTEM.remove(&resolved, false).and_then(|_| FILESYSTEM.create_file(&resolved)),
+        None => FILESYSTEM.create_file(&resolved),
+    })
+}
+
+/// Number of data packets in the last transfer made by `xmodem`.
+static TRANSFER_PACKETS: AtomicUsize = AtomicUsize::new(0);
+
+/// Progress callback for `xmodem` transfers. The console carries the transfer
+/// itself, so anything printed while it's under way would be taken for
+/// protocol bytes by the peer; the packet count is recorded instead, to be
+/// reported once the transfer is over.
+fn record_transfer(progress: Progress) {
+    if let Progress::Completed { packets, .. } = progress {
+        TRANSFER_PACKETS.store(packets, Ordering::Relaxed);
+    }
+}
+
+/// Transfers a file over the console with XMODEM: `xmodem send FILE` sends
+/// it to the peer and `xmodem recv FILE` replaces it with what the peer
+/// sends. A received file keeps the zero padding of the final packet, since
+/// XMODEM doesn't carry the length of the data.
+fn xmodem(args: &[&str], cwd: &Path) {
+    let (send, path) = match args {
+        ["send", path] => (true, *path),
+        ["recv", path] => (false, *path),
+        _ => return kprintln!("xmodem: usage: xmodem send|recv FILE"),
+    };
+
+    TRANSFER_PACKETS.store(0, Ordering::Relaxed);
+    let result = if send {
+        let file = match open("xmodem", cwd, path).map(|entry| entry.into_file()) {
+            Some(Some(file)) => file,
+            Some(None) => return kprintln!("xmodem: {}: is a directory", path),
+            None => return,
+        };
+
+        kprintln!("xmodem: sending {}; start receiving on the host", path);
+        Xmodem::transmit_with_progress(file, &mut *CONSOLE.lock(), record_transfer)
+    } else {
+        let mut file = match open_for_writing("xmodem", cwd, path, false) {
+            Some(Ok(file)) => file,
+            Some(Err(e)) => return print_write_error("xmodem", path, e),
+            None => return,
+        };
+
+        kprintln!("xmodem: receiving {}; start sending on the host", path);
+        // The console has no read timeout to fall back to checksums on, so
+        // the receiver asks for them from the start.
+        Xmodem::new_with_progress(&mut *CONSOLE.lock(), record_transfer)
+            .recv(&mut file)
+            .and_then(|n| file.sync().map(|_| n))
+    };
+
+    let packets = TRANSFER_PACKETS.load(Ordering::Relaxed);
+    match result {
+        Ok(n) if send => kprintln!("xmodem: sent {} bytes in {} packets", n, packets),
+        Ok(n) => kprintln!("xmodem: received {} bytes in {} packets into {}", n, packets, path),
+        Err(e) => kprintln!("xmodem: transfer failed: {}", e),
+    }
+}
+
+/// Size of the buffer holding the line being searched by `grep`.
+const GREP_LINE_CAPACITY: usize = 256;
+
+/// Reads `reader` line by line and calls `f` with the number, counting from 1,
+/// and contents, without the newline, of each line containing `pattern`. Lines
+/// longer than `GREP_LINE_CAPACITY` bytes are searched in pieces of that size,
+/// so a match straddling two pieces isn't found.
+fn grep_lines<R: io::Read>(mut reader: R, pattern: &[u8], f: &mut dyn FnMut(usize, &[u8])) -> io::Result<()> {
+    let mut storage = [0u8; GREP_LINE_CAPACITY];
+    let mut line = StackVec::new(&mut storage);
+    let mut number = 1;
+    let mut search = |line: &[u8], number: usize| {
+        if pattern.is_empty() || line.windows(pattern.len()).any(|window| window == pattern) {
+            f(number, line);
+        }
+    };
+
+    let mut chunk = [0u8; CAT_CHUNK_SIZE];
+    loop {
+        let n = match reader.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+
+        for &byte in &chunk[..n] {
+            if byte == b'\n' {
+                search(&line, number);
+                line.clear();
+                number += 1;
+            } else if let Err(byte) = line.push(byte) {
+                search(&line, number);
+                line.clear();
+                let _ = line.push(byte);
+            }
+        }
+    }
+
+    if !line.is_empty() {
+        search(&line, number);
+    }
+
+    Ok(())
+}
+
+/// Prints the lines of a file containing a pattern: `grep [-n] PATTERN FILE`.
+/// With `-n`, each line is prefixed with its line number.
+fn grep(args: &[&str], cwd: &Path) {
+    let (numbered, args) = match args {
+        ["-n", rest @ ..] => (true, rest),
+        _ => (false, args),
+    };
+
+    let (pattern, path) = match args {
+        [pattern, path] => (*pattern, *path),
+        [] | [_] => return kprintln!("grep: usage: grep [-n] PATTERN FILE"),
+        _ => return kprintln!("grep: too many arguments"),
+    };
+
+    let file = match open("grep", cwd, path).map(|entry| entry.into_file()) {
+        Some(Some(file)) => file,
+        Some(None) => return kprintln!("grep: {}: is a directory", path),
+        None => return,
+    };
+
+    let result = grep_lines(file, pattern.as_bytes(), &mut |number, line| {
+        if numbered {
+            kprint!("{}:", number);
+        }
+
+        let mut console = CONSOLE.lock();
+        line.iter().for_each(|&byte| console.write_byte(byte));
+        console.write_byte(b'\n');
+    });
+
+    if let Err(e) = result {
+        kprintln!("grep: {}: {}", path, e);
+    }
+}
+
+/// Line, word and byte counts of a stream fed to `wc` in chunks.
+#[derive(Debug, Default, PartialEq)]
+struct Counts {
+    lines: usize,
+    words: usize,
+    bytes: usize,
+    /// Whether the last byte fed was part of a word, so a word split across
+    /// two chunks is only counted once.
+    in_word: bool,
+}
+
+impl Counts {
+    /// Counts the newlines, whitespace-delimited words and bytes in `chunk`.
+    fn feed(&mut self, chunk: &[u8]) {
+        for &byte in chunk {
+            if byte == b'\n' {
+                self.lines += 1;
+            }
+
+            if byte.is_ascii_whitespace() {
+                self.in_word = false;
+            } else if !self.in_word {
+                self.in_word = true;
+                self.words += 1;
+            }
+        }
+
+        self.bytes += chunk.len();
+    }
+}
+
+/// Prints the number of lines, words and bytes in a file: `wc [-lwc] FILE`.
+/// The flags select which counts are printed; all three are by default.
+fn wc(args: &[&str], cwd: &Path) {
+    let (mut lines, mut words, mut bytes) = (false, false, false);
+    let mut paths = args;
+    while let [arg, rest @ ..] = paths {
+        let flags = match arg.strip_prefix('-') {
+            Some(flags) if !flags.is_empty() && flags.bytes().all(|c| b"lwc".contains(&c)) => flags,
+            _ => break,
+        };
+
+        lines |= flags.contains('l');
+        words |= flags.contains('w');
+        bytes |= flags.contains('c');
+        paths = rest;
+    }
+
+    if !(lines || words || bytes) {
+        lines = true;
+        words = true;
+        bytes = true;
+    }
+
+    let path = match paths {
+        [path] => *path,
+        [] => return kprintln!("wc: missing file operand"),
+        _ => return kprintln!("wc: too many arguments"),
+    };
+
+    let mut file = match open("wc", cwd, path).map(|entry| entry.into_file()) {
+        Some(Some(file)) => file,
+        Some(None) => return kprintln!("wc: {}: is a directory", path),
+        None => return,
+    };
+
+    let mut counts = Counts::default();
+    let mut chunk = [0u8; CAT_CHUNK_SIZE];
+    loop {
+        match file.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(n) => counts.feed(&chunk[..n]),
+            Err(e) => return kprintln!("wc: {}: {}", path, e),
+        }
+    }
+
+    if lines {
+        kprint!("{:>7} ", counts.lines);
+    }
+    if words {
+        kprint!("{:>7} ", counts.words);
+    }
+    if bytes {
+        kprint!("{:>7} ", counts.bytes);
+    }
+    kprintln!("{}", path);
+}
+
+/// Number of bytes shown on each row of `hexdump` output.
+const HEXDUMP_ROW_SIZE: usize = 16;
+
+/// A row of `hexdump` output: the offset of `bytes`, the bytes in hex, and the
+/// bytes as ASCII with non-printable bytes shown as `.`.
+struct HexRow<'a> {
+    offset: usize,
+    bytes: &'a [u8],
+}
+
+impl fmt::Display for HexRow<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:08x} ", self.offset)?;
+        for i in 0..HEXDUMP_ROW_SIZE {
+            if i % 8 == 0 {
+                write!(f, " ")?;
+            }
+
+            match self.bytes.get(i) {
+                Some(byte) => write!(f, "{:02x} ", byte)?,
+                None => write!(f, "   ")?,
+            }
+        }
+
+        write!(f, " |")?;
+        for &byte in self.bytes {
+            let printable = byte.is_ascii_graphic() || byte == b' ';
+            write!(f, "{}", if printable { byte as char } else { '.' })?;
+        }
+
+        write!(f, "|")
+    }
+}
+
+/// Prints a hexdump of the file in `args`, limited to the number of bytes
+/// given with `-n` if there is one.
+fn hexdump(args: &[&str], cwd: &Path) {
+    let (mut path, mut limit) = (None, usize::MAX);
+    let mut args = args.iter();
+    while let Some(&arg) = args.next() {
+        match arg {
+            "-n" => match args.next().map(|count| count.parse()) {
+                Some(Ok(count)) => limit = count,
+                _ => return kprintln!("hexdump: -n requires a byte count"),
+            },
+            _ if path.is_none() => path = Some(arg),
+            _ => return kprintln!("hexdump: too many arguments"),
+        }
+    }
+
+    let path = match path {
+        Some(path) => path,
+        None => return kprintln!("hexdump: missing file operand"),
+    };
+
+    let mut file = match open("hexdump", cwd, path).map(|entry| entry.into_file()) {
+        Some(Some(file)) => file,
+        Some(None) => return kprintln!("hexdump: {}: is a directory", path),
+        None => return,
+    };
+
+    let mut offset = 0;
+    while offset < limit {
+        let mut row = [0u8; HEXDUMP_ROW_SIZE];
+        let want = core::cmp::min(HEXDUMP_ROW_SIZE, limit - offset);
+        let mut len = 0;
+        while len < want {
+            match file.read(&mut row[len..want]) {
+                Ok(0) => break,
+                Ok(n) => len += n,
+                Err(e) => return kprintln!("hexdump: {}: {}", path, e),
+            }
+        }
+
+        if len == 0 {
+            break;
+        }
+
+        kprintln!("{}", HexRow { offset, bytes: &row[..len] });
+        offset += len;
+        if len < want {
+            break;
+        }
+    }
+
+    kprintln!("{:08x}", offset);
+}
+
+/// Default number of lines on each page shown by `less`.
+const LESS_ROWS: usize = 24;
+
+/// The prompt `less` shows at the end of each page.
+const LESS_PROMPT: &[u8] = b"--More--";
+
+/// Copies `reader` to `term` a page of `rows` lines at a time, streaming it
+/// a chunk at a time. At the end of each page, the prompt is shown and a key
+/// read: space shows the next page, Enter the next line and `q` quits. Other
+/// keys ring the bell. Lines count as one row however wide they are.
+fn page<R: io::Read, T: Terminal + ?Sized>(mut reader: R, term: &mut T, rows: usize) -> io::Result<()> {
+    let mut remaining = rows;
+    let mut chunk = [0u8; CAT_CHUNK_SIZE];
+    loop {
+        let n = match reader.read(&mut chunk) {
+            Ok(0) => return Ok(()),
+            Ok(n) => n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+
+        for &byte in &chunk[..n] {
+            // The prompt is only shown once there's more to show.
+            while remaining == 0 {
+                term.write_bytes(LESS_PROMPT);
+                let key = term.read_byte();
+                term.write_byte(b'\r');
+                LESS_PROMPT.iter().for_each(|_| term.write_byte(b' '));
+                term.write_byte(b'\r');
+
+                remaining = match key {
+                    b' ' => rows,
+                    b'\r' | b'\n' => 1,
+                    b'q' => return Ok(()),
+                    _ => {
+                        term.write_byte(7u8); // rings the bell
+                        0
+                    }
+                };
+            }
+
+            term.write_byte(byte);
+            if byte == b'\n' {
+                remaining -= 1;
+            }
+        }
+    }
+}
+
+/// Shows a file a page at a time on `term`: `less [-r ROWS] FILE`. Pages are
+/// `LESS_ROWS` lines long unless `-r` says otherwise, since the height of the
+/// terminal isn't known.
+fn less(args: &[&str], cwd: &Path, term: &mut dyn Terminal) {
+    let (rows, args) = match args {
+        ["-r", rows, rest @ ..] => match rows.parse() {
+            Ok(rows) if rows > 0 => (rows, rest),
+            _ => return kprintln!("less: -r requires a positive number of rows"),
+        },
+        ["-r"] => return kprintln!("less: -r requires a positive number of rows"),
+        _ => (LESS_ROWS, args),
+    };
+
+    let path = match args {
+        [path] => *path,
+        [] => return kprintln!("less: missing file operand"),
+        _ => return kprintln!("less: too many arguments"),
+    };
+
+    let file = match open("less", cwd, path).map(|entry| entry.into_file()) {
+        Some(Some(file)) => file,
+        Some(None) => return kprintln!("less: {}: is a directory", path),
+        None => return,
+    };
+
+    if let Err(e) = page(file, term, rows) {
+        kprintln!("less: {}: {}", path, e);
+    }
+}
+
+/// Maximum number of shell variables.
+const VARIABLES_CAPACITY: usize = 16;
+
+/// Maximum length in bytes of a variable's name.
+const VARIABLE_NAME_CAPACITY: usize = 32;
+
+/// Maximum length in bytes of a variable's value.
+const VARIABLE_VALUE_CAPACITY: usize = 128;
+
+/// A shell variable set with `set`.
+#[derive(Clone, Copy)]
+struct Variable {
+    name: [u8; VARIABLE_NAME_CAPACITY],
+    name_len: usize,
+    value: [u8; VARIABLE_VALUE_CAPACITY],
+    value_len: usize,
+}
+
+impl Variable {
+    /// A variable with an empty name and value.
+    const EMPTY: Variable = Variable {
+        name: [0; VARIABLE_NAME_CAPACITY],
+        name_len: 0,
+        value: [0; VARIABLE_VALUE_CAPACITY],
+        value_len: 0,
+    };
+
+    /// Returns the variable's name.
+    fn name(&self) -> &str {
+        core::str::from_utf8(&self.name[..self.name_len]).unwrap_or("")
+    }
+
+    /// Returns the variable's value.
+    fn value(&self) -> &str {
+        core::str::from_utf8(&self.value[..self.value_len]).unwrap_or("")
+    }
+}
+
+/// `unset` leaves an empty variable in the slot it frees.
+impl Default for Variable {
+    fn default() -> Variable {
+        Variable::EMPTY
+    }
+}
+
+/// Returns `true` if `name` can name a variable: it's made of ASCII letters,
+/// digits and underscores, and doesn't start with a digit.
+fn is_variable_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Returns the value of the variable `name` in `vars`, if it's set.
+fn lookup<'v>(vars: &'v StackVec<Variable>, name: &str) -> Option<&'v str> {
+    vars.iter().find(|var| var.name() == name).map(Variable::value)
+}
+
+/// Sets the variable `name` in `vars` to `value`, replacing any previous
+/// value.
+fn set_variable(
+    vars: &mut StackVec<Variable>,
+    name: &str,
+    value: &str,
+) -> Result<(), &'static str> {
+    if !is_variable_name(name) {
+        return Err("invalid variable name");
+    } else if name.len() > VARIABLE_NAME_CAPACITY {
+        return Err("variable name is too long");
+    } else if value.len() > VARIABLE_VALUE_CAPACITY {
+        return Err("value is too long");
+    }
+
+    let index = match vars.iter().position(|var| var.name() == name) {
+        Some(index) => index,
+        None => {
+            vars.push(Variable::EMPTY).map_err(|_| "too many variables")?;
+            vars.len() - 1
+        }
+    };
+
+    let var = &mut vars[index];
+    var.name[..name.len()].copy_from_slice(name.as_bytes());
+    var.name_len = name.len();
+    var.value[..value.len()].copy_from_slice(value.as_bytes());
+    var.value_len = value.len();
+    Ok(())
+}
+
+/// Removes the variable `name` from `vars`. Returns `false` if it wasn't set.
+fn unset_variable(vars: &mut StackVec<Variable>, name: &str) -> bool {
+    match vars.iter().position(|var| var.name() == name) {
+        Some(index) => {
+            vars.remove(index);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Copies the argument `arg`, as split off by `next_arg`, into `out` with its
+/// quotes and escapes removed and each `$NAME` and `${NAME}`, quoted or not,
+/// replaced with the value of the variable `NAME` in `vars`, or with nothing
+/// if it isn't set. A `$` that isn't followed by a variable name is copied as
+/// is. Values are copied literally: quotes, whitespace, `>` or `$` in a value
+/// are never interpreted.
+///
+/// # Errors
+///
+/// Returns `Error::TooLong` if the expansion doesn't fit in `out`.
+fn expand(arg: &str, vars: &StackVec<Variable>, out: &mut StackVec<u8>) -> Result<(), Error> {
+    let is_name_char = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let (mut rest, mut quoted) = (arg, false);
+    while let Some(i) = rest.find(['"', '\\', '$']) {
+        out.extend_from_slice(&rest.as_bytes()[..i]).map_err(|_| Error::TooLong)?;
+        let after = &rest[i + 1..];
+        rest = match rest.as_bytes()[i] {
+            b'"' => {
+                quoted = !quoted;
+                after
+            }
+            b'\\' if quoted && after.starts_with('"') => {
+                out.push(b'"').map_err(|_| Error::TooLong)?;
+                &after[1..]
+            }
+            b'\\' => {
+                out.push(b'\\').map_err(|_| Error::TooLong)?;
+                after
+            }
+            _ => {
+                let braced = after.strip_prefix('{').and_then(|braced| Some((braced, braced.find('}')?)));
+                let (name, tail) = match braced {
+                    Some((braced, end)) => (&braced[..end], &braced[end + 1..]),
+                    None => {
+                        let end = after.find(|c: char| !is_name_char(c)).unwrap_or(after.len());
+                        (&after[..end], &after[end..])
+                    }
+                };
+
+                if is_variable_name(name) {
+                    let value = lookup(vars, name).unwrap_or("");
+                    out.extend_from_slice(value.as_bytes()).map_err(|_| Error::TooLong)?;
+                    tail
+                } else {
+                    out.push(b'$').map_err(|_| Error::TooLong)?;
+                    after
+                }
+            }
+        };
+    }
+
+    out.extend_from_slice(rest.as_bytes()).map_err(|_| Error::TooLong)
+}
+
+/// Sets the variable `args[0]` to `args[1]`, or to the empty string if no
+/// value is given.
+fn set(args: &[&str], vars: &mut StackVec<Variable>) {
+    let (name, value) = match args {
+        [name] => (*name, ""),
+        [name, value] => (*name, *value),
+        [] => return kprintln!("set: missing variable name"),
+        _ => return kprintln!("set: too many arguments"),
+    };
+
+    if let Err(e) = set_variable(vars, name, value) {
+        kprintln!("set: {}: {}", name, e);
+    }
+}
+
+/// Removes each variable in `args`.
+fn unset(args: &[&str], vars: &mut StackVec<Variable>) {
+    if args.is_empty() {
+        return kprintln!("unset: missing variable name");
+    }
+
+    for name in args {
+        if !unset_variable(vars, name) {
+            kprintln!("unset: {}: not set", name);
+        }
+    }
+}
+
+/// Prints each variable in `vars` as `NAME=value`.
+fn env(vars: &StackVec<Variable>) {
+    for var in vars.iter() {
+        kprintln!("{}={}", var.name(), var.value());
+    }
+}
+
+/// Executes `command` with `cwd` as the current working directory,
+/// `custom_prompt` as the prompt set with the `prompt` command and `vars` as
+/// the shell variables.
+fn execute(
+    command: &Command,
+    cwd: &mut PathBuf,
+    custom_prompt: &mut StackVec<u8>,
+    vars: &mut StackVec<Variable>,
+    history: &StackVec<HistoryEntry>,
+    term: &mut dyn Terminal,
+) {
+    let args = &command.args.as_slice()[1..];
+    if let Some(ref redirect) = command.redirect {
+        return match command.path() {
+            "echo" => write_redirected("echo", redirect, cwd, format_args!("{}", Echo::new(args))),
+            path => kprintln!("{}: output redirection is only supported for echo", path),
+        };
+    }
+
+    match command.path() {
+        "echo" => kprint!("{}", Echo::new(args)),
+        "welcome" => kprintln!("{}", WELCOME_TXT),
+        "pwd" => pwd(cwd),
+        "cd" => cd(args, cwd),
+        "ls" => ls(args, cwd),
+        "cat" => cat(args, cwd),
+        "hexdump" => hexdump(args, cwd),
+        "less" => less(args, cwd, term),
+        "grep" => grep(args, cwd),
+        "find" => find(args, cwd),
+        "du" => du(args, cwd),
+        "stat" => stat(args, cwd),
+        "mkdir" => mkdir(args, cwd),
+        "touch" => touch(args, cwd),
+        "rm" => rm(args, cwd),
+        "help" => help(),
+        "history" => print_history(history),
+        "clear" => clear(),
+        "atags" => atags(),
+        "sleep" => sleep(args),
+        "uptime" => uptime(),
+        "date" => date(),
+        "wc" => wc(args, cwd),
+        "which" => which(args),
+        "xmodem" => xmodem(args, cwd),
+        "reboot" => reboot(),
+        "prompt" => prompt(args, custom_prompt),
+        "set" => set(args, vars),
+        "unset" => unset(args, vars),
+        "env" => env(vars),
+        path => {
+            kprintln!("unknown command: {}", path);
+            help();
+        }
+    }
+}
+
+/// Number of command lines remembered by the shell's history.
+const HISTORY_CAPACITY: usize = 16;
+
+/// A command line saved in the shell's history.
+#[derive(Clone, Copy)]
+struct HistoryEntry {
+    bytes: [u8; MAX_LINE_LENGTH],
+    len: usize,
+}
+
+impl HistoryEntry {
+    /// An entry holding the empty line.
+    const EMPTY: HistoryEntry = HistoryEntry { bytes: [0; MAX_LINE_LENGTH], len: 0 };
+
+    /// Returns an entry holding a copy of `line`.
+    fn new(line: &[u8]) -> HistoryEntry {
+        let mut entry = HistoryEntry::EMPTY;
+        entry.bytes[..line.len()].copy_from_slice(line);
+        entry.len = line.len();
+        entry
+    }
+
+    /// Returns the saved line.
+    fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+}
+
+/// Entries dropped from a full history leave the empty line in their slot.
+impl Default for HistoryEntry {
+    fn default() -> HistoryEntry {
+        HistoryEntry::EMPTY
+    }
+}
+
+/// Appends `line` to `history`, discarding the oldest entry if `history` is
+/// full. Empty lines, lines longer than `MAX_LINE_LENGTH` and repeats of the
+/// most recent entry aren't recorded.
+fn remember(history: &mut StackVec<HistoryEntry>, line: &[u8]) {
+    let repeated = history.last().map(HistoryEntry::as_bytes) == Some(line);
+    if line.is_empty() || line.len() > MAX_LINE_LENGTH || repeated {
+        return;
+    }
+
+    if history.is_full() {
+        history.remove(0);
+    }
+
+    let _ = history.push(HistoryEntry::new(line));
+}
+
+/// The file the shell saves its history to on `exit`, and loads it from at
+/// startup.
+const HISTORY_FILE: &str = "/.sh_history";
+
+/// Prints each entry of `history`, numbered from 1 for use with `!N`.
+fn print_history(history: &StackVec<HistoryEntry>) {
+    for (i, entry) in history.iter().enumerate() {
+        let line = core::str::from_utf8(entry.as_bytes()).unwrap_or("(not UTF-8)");
+        kprintln!("{:>5}  {}", i + 1, line);
+    }
+}
+
+/// Returns `N` if `line` is the history reference `!N`.
+fn history_event(line: &[u8]) -> Option<usize> {
+    let digits = line.trim_ascii().strip_prefix(b"!")?;
+    if digits.is_empty() || !digits.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+
+    core::str::from_utf8(digits).ok()?.parse().ok()
+}
+
+/// Remembers each line read from `reader` in `history`, so only the last
+/// `history.capacity()` lines are kept. Lines longer than `MAX_LINE_LENGTH`
+/// are skipped.
+fn load_history<R: io::Read>(mut reader: R, history: &mut StackVec<HistoryEntry>) -> io::Result<()> {
+    let mut storage = [0u8; MAX_LINE_LENGTH];
+    let mut line = StackVec::new(&mut storage);
+    let mut overlong = false;
+
+    let mut chunk = [0u8; CAT_CHUNK_SIZE];
+    loop {
+        let n = match reader.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+
+        for &byte in &chunk[..n] {
+            if byte == b'\n' {
+                if !overlong {
+                    remember(history, &line);
+                }
+                line.clear();
+                overlong = false;
+            } else if line.push(byte).is_err() {
+                overlong = true;
+            }
+        }
+    }
+
+    if !overlong {
+        remember(history, &line);
+    }
+
+    Ok(())
+}
+
+/// Writes each entry of `history` to `writer`, one per line.
+fn save_history<W: io::Write>(mut writer: W, history: &StackVec<HistoryEntry>) -> io::Result<()> {
+    for entry in history.iter() {
+        writer.write_all(entry.as_bytes())?;
+        writer.write_all(b"\n")?;
+    }
+
+    Ok(())
+}
+
+/// Replaces `HISTORY_FILE` with `history`. The file is a convenience, so a
+/// volume that can't be written to, e.g. because it's read-only, isn't
+/// reported.
+fn persist_history(history: &StackVec<HistoryEntry>) {
+    let path = Path::new(HISTORY_FILE);
+    let _ = match FILESYSTEM.remove(path, false) {
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+    .and_then(|_| FILESYSTEM.create_file(path))
+    .and_then(|mut file| save_history(&mut file, history).and_then(|_| file.sync()));
+}
+
+/// A byte-oriented terminal that the line editor reads keys from and echoes
+/// to.
+trait Terminal {
+    /// Reads a byte, blocking until one is available.
+    fn read_byte(&mut self) -> u8;
+
+    /// Writes the byte `byte`.
+    fn write_byte(&mut self, byte: u8);
+
+    /// Writes each byte of `bytes`.
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        bytes.iter().for_each(|&byte| self.write_byte(byte));
+    }
+}
+
+impl Terminal for Console {
+    fn read_byte(&mut self) -> u8 {
+        Console::read_byte(self)
+    }
+
+    fn write_byte(&mut self, byte: u8) {
+        Console::write_byte(self, byte)
+    }
+}
+
+/// A terminal that hides the second byte of a `\r\n` or `\n\r` line break, so
+/// that a terminal sending both doesn't produce an empty line after each line.
+/// Since the last line break is tracked across reads, it must outlive the
+/// individual calls to `read_line`.
+struct LineEndings<'a, T> {
+    term: &'a mut T,
+    /// The byte that ended the last line, if it was the last byte read.
+    last_break: Option<u8>,
+}
+
+impl<'a, T: Terminal> LineEndings<'a, T> {
+    fn new(term: &'a mut T) -> LineEndings<'a, T> {
+        LineEndings { term, last_break: None }
+    }
+}
+
+impl<T: Terminal> Terminal for LineEndings<'_, T> {
+    fn read_byte(&mut self) -> u8 {
+        loop {
+            let byte = self.term.read_byte();
+            let swallowed = matches!((self.last_break, byte), (Some(b'\r'), b'\n') | (Some(b'\n'), b'\r'));
+
+            // A swallowed byte doesn't end a line itself, so the one after it
+            // is never swallowed: `\r\n\r\n` is two line breaks.
+            self.last_break = match byte {
+                b'\r' | b'\n' if !swallowed => Some(byte),
+                _ => None,
+            };
+
+            if !swallowed {
+                return byte;
+            }
+        }
+    }
+
+    fn write_byte(&mut self, byte: u8) {
+        self.term.write_byte(byte)
+    }
+}
+
+/// Storage for the line being edited by `read_line`.
+trait LineBuffer: core::ops::DerefMut<Target = [u8]> {
+    /// Inserts `bytes` at `index`. Returns `false`, leaving the line
+    /// untouched, if they don't fit.
+    fn insert_slice(&mut self, index: usize, bytes: &[u8]) -> bool;
+
+    /// Removes and returns the byte at `index`.
+    fn remove(&mut self, index: usize) -> u8;
+
+    /// Empties the line.
+    fn clear(&mut self);
+}
+
+impl<'a> LineBuffer for StackVec<'a, u8> {
+    fn insert_slice(&mut self, index: usize, bytes: &[u8]) -> bool {
+        if self.len() + bytes.len() > self.capacity() {
+            return false;
+        }
+
+        for (i, &byte) in bytes.iter().enumerate() {
+            let _ = self.insert(index + i, byte);
+        }
+
+        true
+    }
+
+    fn remove(&mut self, index: usize) -> u8 {
+        StackVec::remove(self, index)
+    }
+
+    fn clear(&mut self) {
+        StackVec::clear(self)
+    }
+}
+
+/// A line kept in a `StackVec` until it outgrows it, after which it spills
+/// into a heap-allocated buffer, so its length is only limited by memory.
+#[cfg(feature = "alloc")]
+enum SpillLine<'a> {
+    Stack(StackVec<'a, u8>),
+    Heap(Vec<u8>),
+}
+
+#[cfg(feature = "alloc")]
+impl<'a> SpillLine<'a> {
+    /// Returns a line that is kept in `storage` while it fits.
+    fn new(storage: &'a mut [u8]) -> SpillLine<'a> {
+        SpillLine::Stack(StackVec::new(storage))
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a> core::ops::Deref for SpillLine<'a> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            SpillLine::Stack(line) => line,
+            SpillLine::Heap(line) => line,
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a> core::ops::DerefMut for SpillLine<'a> {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        match self {
+            SpillLine::Stack(line) => line,
+            SpillLine::Heap(line) => line,
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a> LineBuffer for SpillLine<'a> {
+    fn insert_slice(&mut self, index: usize, bytes: &[u8]) -> bool {
+        if let SpillLine::Stack(line) = self {
+            if line.insert_slice(index, bytes) {
+                return true;
+            }
+
+            let mut heap = Vec::with_capacity(2 * (line.len() + bytes.len()));
+            heap.extend_from_slice(line);
+            *self = SpillLine::Heap(heap);
+        }
+
+        match self {
+            SpillLine::Heap(line) => {
+                line.splice(index..index, bytes.iter().copied());
+                true
+            }
+            SpillLine::Stack(_) => unreachable!("line has spilled"),
+        }
+    }
+
+    fn remove(&mut self, index: usize) -> u8 {
+        match self {
+            SpillLine::Stack(line) => StackVec::remove(line, index),
+            SpillLine::Heap(line) => line.remove(index),
+        }
+    }
+
+    fn clear(&mut self) {
+        match self {
+            SpillLine::Stack(line) => StackVec::clear(line),
+            SpillLine::Heap(line) => line.clear(),
+        }
+    }
+}
+
+/// A source of candidates for tab completion.
+trait Completer {
+    /// Calls `f` with the name of each candidate for the last component of
+    /// `word`, along with whether the candidate is a directory. `command` is
+    /// `true` if `word` is in the command position of the line. Candidates
+    /// needn't start with the component; the line editor filters them.
+    fn complete(&self, word: &str, command: bool, f: &mut dyn FnMut(&str, bool));
+}
+
+/// Completes command names against the built-in commands and arguments
+/// against the entries of the directory they name, relative to `cwd`.
+struct ShellCompleter<'a> {
+    cwd: &'a Path,
+}
+
+impl Completer for ShellCompleter<'_> {
+    fn complete(&self, word: &str, command: bool, f: &mut dyn FnMut(&str, bool)) {
+        if command {
+            COMMANDS.iter().for_each(|(name, _)| f(name, false));
+            return;
+        }
+
+        let (dir, partial) = match word.rfind('/') {
+            Some(i) => (&word[..=i], &word[i + 1..]),
+            None => (".", word),
+        };
+
+        let entries = match FILESYSTEM.open_dir(resolve(self.cwd, dir)).and_then(|d| d.entries()) {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+
+        for entry in entries {
+            let name = entry.name();
+            let hidden = entry.metadata().hidden() || name.starts_with('.');
+            if name == "." || name == ".." || (hidden && !partial.starts_with('.')) {
+                continue;
+            }
+
+            f(name, entry.is_dir());
+        }
+    }
+}
+
+/// Returns the length of the UTF-8 sequence starting with `lead`, or `None` if
+/// `lead` can't start a sequence.
+fn utf8_width(lead: u8) -> Option<usize> {
+    match lead {
+        0x00..=0x7f => Some(1),
+        0xc2..=0xdf => Some(2),
+        0xe0..=0xef => Some(3),
+        0xf0..=0xf4 => Some(4),
+        _ => None,
+    }
+}
+
+/// Returns the number of terminal columns taken up by the UTF-8 `bytes`,
+/// assuming each character takes a single column.
+fn columns(bytes: &[u8]) -> usize {
+    bytes.iter().filter(|&&byte| byte & 0xc0 != 0x80).count()
+}
+
+/// Returns the index of the character boundary in `line` before `cursor`.
+fn prev_boundary(line: &[u8], cursor: usize) -> usize {
+    let mut i = cursor.saturating_sub(1);
+    while i > 0 && line[i] & 0xc0 == 0x80 {
+        i -= 1;
+    }
+
+    i
+}
+
+/// Returns the index of the character boundary in `line` after `cursor`.
+fn next_boundary(line: &[u8], cursor: usize) -> usize {
+    let mut i = cursor + 1;
+    while i < line.len() && line[i] & 0xc0 == 0x80 {
+        i += 1;
+    }
+
+    i
+}
+
+/// Reads the rest of the UTF-8 encoded character starting with `lead` into
+/// `buf` and returns its encoding, or `None` if the sequence is invalid.
+fn read_char<'a, T: Terminal>(term: &mut T, lead: u8, buf: &'a mut [u8; 4]) -> Option<&'a [u8]> {
+    let width = utf8_width(lead)?;
+    buf[0] = lead;
+    for byte in &mut buf[1..width] {
+        *byte = term.read_byte();
+    }
+
+    let encoded = &buf[..width];
+    core::str::from_utf8(encoded).ok().map(|_| encoded)
+}
+
+/// Reads the remainder of an ANSI escape sequence after `ESC` and returns its
+/// final byte. Parameter and intermediate bytes, as in `ESC [ 3 ~`, are read
+/// up to the final byte. Returns `None` if the sequence isn't a CSI sequence
+/// (`ESC [`), carries parameters, or is malformed, so that it is ignored.
+fn read_escape<T: Terminal>(term: &mut T) -> Option<u8> {
+    if term.read_byte() != b'[' {
+        return None;
+    }
+
+    let mut plain = true;
+    loop {
+        match term.read_byte() {
+            0x20..=0x3f => plain = false,
+            byte @ 0x40..=0x7e if plain => return Some(byte),
+            _ => return None,
+        }
+    }
+}
+
+/// Moves the terminal cursor `n` columns to the left.
+fn move_left<T: Terminal>(term: &mut T, n: usize) {
+    for _ in 0..n {
+        term.write_byte(8u8);
+    }
+}
+
+/// Redraws `line` from `cursor` to the end, blanks out the `erased` columns
+/// that followed the previous end of the line, and moves the terminal cursor
+/// back to `cursor`.
+fn redraw_tail<T: Terminal>(term: &mut T, line: &[u8], cursor: usize, erased: usize) {
+    term.write_bytes(&line[cursor..]);
+    for _ in 0..erased {
+        term.write_byte(b' ');
+    }
+
+    move_left(term, columns(&line[cursor..]) + erased);
+}
+
+/// Erases `line` from the terminal and replaces its contents with `bytes`,
+/// echoing the new contents. The terminal cursor is expected to be at column
+/// `cursor` of the line and is left at the end of the new line.
+fn replace_line<T: Terminal, L: LineBuffer>(term: &mut T, line: &mut L, cursor: usize, bytes: &[u8]) {
+    term.write_bytes(&line[cursor..]);
+    for _ in 0..columns(line) {
+        term.write_bytes(&[8u8, b' ', 8u8]);
+    }
+
+    line.clear();
+    line.insert_slice(0, bytes);
+    term.write_bytes(bytes);
+}
+
+/// Inserts `bytes` into `line` at `cursor`, echoing them and redrawing the
+/// rest of the line. Returns `false`, leaving `line` untouched, if they don't
+/// fit.
+fn insert_bytes<T: Terminal, L: LineBuffer>(term: &mut T, line: &mut L, cursor: &mut usize, bytes: &[u8]) -> bool {
+    if !line.insert_slice(*cursor, bytes) {
+        return false;
+    }
+
+    *cursor += bytes.len();
+    term.write_bytes(bytes);
+    redraw_tail(term, line, *cursor, 0);
+    true
+}
+
+/// Completes the word before `cursor` using `completer`. A unique candidate
+/// is inserted in full, followed by `/` for directories and a space
+/// otherwise. When there are several, their longest common prefix is
+/// inserted; if that adds nothing, the candidates are listed and the prompt
+/// and line are redrawn below them. The bell rings if nothing matches.
+fn complete<T: Terminal, L: LineBuffer, C: Completer>(
+    term: &mut T,
+    prompt: &str,
+    line: &mut L,
+    cursor: &mut usize,
+    completer: &C,
+) {
+    let before = &line[..*cursor];
+    let start = before.iter().rposition(u8::is_ascii_whitespace).map_or(0, |i| i + 1);
+    let command = before[..start].iter().all(u8::is_ascii_whitespace);
+    let word = match core::str::from_utf8(&before[start..]) {
+        Ok(word) => word,
+        Err(_) => return term.write_byte(7u8),
+    };
+    let partial = word.rsplit('/').next().unwrap_or(word);
+
+    // the part of the candidates past `partial` that they all share
+    let mut common = [0u8; MAX_LINE_LENGTH + 1];
+    let (mut common_len, mut count, mut dir) = (0, 0, false);
+    completer.complete(word, command, &mut |name, is_dir| {
+        if let Some(rest) = name.strip_prefix(partial) {
+            let rest = rest.as_bytes();
+            if count == 0 {
+                common_len = core::cmp::min(rest.len(), MAX_LINE_LENGTH);
+                common[..common_len].copy_from_slice(&rest[..common_len]);
+                dir = is_dir;
+            } else {
+                common_len = common[..common_len].iter().zip(rest).take_while(|(a, b)| a == b).count();
+            }
+
+            count += 1;
+        }
+    });
+
+    // the shared bytes may end partway through a character
+    if let Err(e) = core::str::from_utf8(&common[..common_len]) {
+        common_len = e.valid_up_to();
+    }
+
+    if count > 1 && common_len == 0 {
+        term.write_byte(b'\n');
+        completer.complete(word, command, &mut |name, is_dir| {
+            if name.starts_with(partial) {
+                term.write_bytes(name.as_bytes());
+                term.write_bytes(if is_dir { b"/  " } else { b"  " });
+            }
+        });
+
+        term.write_byte(b'\n');
+        term.write_bytes(prompt.as_bytes());
+        term.write_bytes(line);
+        move_left(term, columns(&line[*cursor..]));
+        return;
+    }
+
+    if count == 1 {
+        common[common_len] = if dir { b'/' } else { b' ' };
+        common_len += 1;
+    }
+
+    if count == 0 || !insert_bytes(term, line, cursor, &common[..common_len]) {
+        term.write_byte(7u8); // rings the bell
+    }
+}
+
+/// Reads a line from `term` into `line`, echoing it and handling the line
+/// editing keys, until Enter is pressed. The up and down arrows recall entries
+/// of `history` and Tab completes the current word with `completer`. Input is
+/// UTF-8; editing works on whole characters. Keys that can't be handled,
+/// including invalid UTF-8 and characters that don't fit in `line`, ring the
+/// terminal bell. How long the line may grow is up to `line`'s `LineBuffer`
+/// implementation.
+///
+/// `prompt` is printed before the line is read and again when tab completion
+/// redraws the line.
+///
+/// Returns `false` if the line was abandoned with Ctrl-C.
+fn read_line<T: Terminal, L: LineBuffer, C: Completer>(
+    term: &mut T,
+    prompt: &str,
+    line: &mut L,
+    history: &StackVec<HistoryEntry>,
+    completer: &C,
+) -> bool {
+    term.write_bytes(prompt.as_bytes());
+    let mut cursor = 0;
+
+    // position in `history` being shown; `history.len()` is the new line
+    let mut recalled = history.len();
+
+    loop {
+        match term.read_byte() {
+            b'\r' | b'\n' => {
+                term.write_byte(b'\n');
+                return true;
+            }
+            0x03 => {
+                // Ctrl-C: abandon the line
+                term.write_bytes(&line[cursor..]);
+                term.write_bytes(b"^C\n");
+                return false;
+            }
+            0x15 => {
+                // Ctrl-U: erase the whole line
+                replace_line(term, line, cursor, &[]);
+                cursor = 0;
+            }
+            8 | 127 => {
+                if cursor != 0 {
+                    let start = prev_boundary(line, cursor);
+                    while cursor > start {
+                        cursor -= 1;
+                        line.remove(cursor);
+                    }
+
+                    move_left(term, 1);
+                    redraw_tail(term, line, cursor, 1);
+                }
+            }
+            b'\t' => complete(term, prompt, line, &mut cursor, completer),
+            0x1b => match read_escape(term) {
+                Some(b'A') if recalled > 0 => {
+                    recalled -= 1;
+                    replace_line(term, line, cursor, history[recalled].as_bytes());
+                    cursor = line.len();
+                }
+                Some(b'B') if recalled < history.len() => {
+                    recalled += 1;
+                    let bytes = match history.get(recalled) {
+                        Some(entry) => entry.as_bytes(),
+                        None => &[],
+                    };
+                    replace_line(term, line, cursor, bytes);
+                    cursor = line.len();
+                }
+                Some(b'C') if cursor < line.len() => {
+                    let next = next_boundary(line, cursor);
+                    term.write_bytes(&line[cursor..next]);
+                    cursor = next;
+                }
+                Some(b'D') if cursor > 0 => {
+                    move_left(term, 1);
+                    cursor = prev_boundary(line, cursor);
+                }
+                _ => {}
+            },
+            lead => match read_char(term, lead, &mut [0; 4]) {
+                Some(encoded) if insert_bytes(term, line, &mut cursor, encoded) => {}
+                _ => {
+                    term.write_byte(7u8); // rings the bell
+                }
+            },
+        }
+    }
+}
+
+const MAX_LINE_LENGTH: usize = 512;
+
+/// Starts a shell using `prefix` as the prefix for each line. This function
+/// returns if the `exit` command is called.
+pub fn shell(prefix: &str) {
+    kprintln!("{}", WELCOME_TXT);
+
+    let mut cwd = PathBuf::from("/");
+    let mut history_storage = [HistoryEntry::EMPTY; HISTORY_CAPACITY];
+    let mut history = StackVec::new(&mut history_storage);
+    let mut custom_prompt_storage = [0; PROMPT_CAPACITY];
+    let mut custom_prompt = StackVec::new(&mut custom_prompt_storage);
+    let mut variables_storage = [Variable::EMPTY; VARIABLES_CAPACITY];
+    let mut variables = StackVec::new(&mut variables_storage);
+    // A shell without a saved history simply starts with an empty one.
+    if let Some(file) = FILESYSTEM.open(HISTORY_FILE).ok().and_then(|entry| entry.into_file()) {
+        let _ = load_history(file, &mut history);
+    }
+
+    let mut console = CONSOLE.lock();
+    let mut input = LineEndings::new(&mut *console);
+    loop {
+        let mut prompt_storage = [0; MAX_LINE_LENGTH];
+        let mut prompt: StackVec<u8> = StackVec::new(&mut prompt_storage);
+        // A prompt too long for the buffer is cut short.
+        let _ = write_prompt(&mut prompt, prefix, custom_prompt.as_str().unwrap_or(""), &cwd);
+
+        let mut storage = [0; MAX_LINE_LENGTH]; // maxiumum command size
+        #[cfg(not(feature = "alloc"))]
+        let mut line: StackVec<u8> = StackVec::new(&mut storage);
+        #[cfg(feature = "alloc")]
+        let mut line = SpillLine::new(&mut storage);
+        let completer = ShellCompleter { cwd: &cwd };
+        let prompt = prompt.as_str().unwrap_or(prefix);
+        if !read_line(&mut input, prompt, &mut line, &history, &completer) {
+            continue;
+        }
+
+        if let Some(event) = history_event(&line) {
+            let entry = match event.checked_sub(1).and_then(|i| history.get(i)) {
+                Some(entry) => entry.as_bytes(),
+                None => {
+                    kprintln!("!{}: event not found", event);
+                    continue;
+                }
+            };
+
+            // The recalled command is shown before it runs.
+            kprintln!("{}", core::str::from_utf8(entry).unwrap_or("(not UTF-8)"));
+            line.clear();
+            let _ = line.insert_slice(0, entry);
+        }
+
+        remember(&mut history, &line);
+        let mut sequence = match core::str::from_utf8_mut(&mut line) {
+            Ok(sequence) => Some(sequence),
+            Err(_) => {
+                kprintln!("error: input is not valid UTF-8");
+                continue;
+            }
+        };
+
+        // Commands run in order; a command that can't be parsed stops the
+        // rest of the sequence.
+        while let Some(rest) = sequence {
+            let (command_string, rest) = next_command(rest);
+            sequence = rest;
+
+            // Variables are expanded as each command is parsed, so that a
+            // command sees the variables set by the ones before it.
+            #[cfg(not(feature = "alloc"))]
+            let mut expanded_storage = [0; MAX_LINE_LENGTH];
+            // A spilled line leaves the same room for expansion as any other.
+            #[cfg(feature = "alloc")]
+            let mut expanded_storage = alloc::vec![0; command_string.len() + MAX_LINE_LENGTH];
+            let mut buf = [""; 64];
+            match Command::parse(command_string, &variables, &mut expanded_storage, &mut buf) {
+                Ok(ref command) if command.path() == "exit" => return persist_history(&history),
+                Ok(command) => {
+                    execute(&command, &mut cwd, &mut custom_prompt, &mut variables, &history, &mut input)
+                }
+                Err(Error::Empty) => {}
+                Err(Error::TooManyArgs { capacity, count }) => {
+                    kprintln!("error: too many arguments ({} given, max {})", count, capacity);
+                    break;
+                }
+                Err(Error::UnterminatedQuote) => {
+                    kprintln!("error: unterminated quote");
+                    break;
+                }
+                Err(Error::BadRedirect) => {
+                    kprintln!("error: expected a single file name after the redirection");
+                    break;
+                }
+                Err(Error::TooLong) => {
+                    kprintln!("error: command is too long once variables are expanded");
+                    break;
+                }
+            }
         }
     }
 }
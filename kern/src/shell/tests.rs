@@ -0,0 +1,953 @@
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use fat32::traits::{Metadata, Timestamp};
+use stack_vec::StackVec;
+
+use super::{
+    describe, disk_usage, find_args, grep_lines, history_event, load_history, lookup, next_command,
+    page, read_line, remember, save_history, set_variable, sleep_duration, stat_path,
+    unset_variable, walk_usage, write_prompt, Clock, Command, Completer, Counts, Echo, EntryKind,
+    Error, FindArgs, HexRow, HistoryEntry, IsoTime, LineEndings, Redirect, Stat, Terminal, Uptime,
+    Variable, COMMANDS, GREP_LINE_CAPACITY, MAX_LINE_LENGTH, VARIABLE_VALUE_CAPACITY,
+};
+
+/// A terminal that replays scripted input and records everything echoed.
+struct MockTerminal {
+    input: VecDeque<u8>,
+    output: Vec<u8>,
+}
+
+impl MockTerminal {
+    fn new(input: &[u8]) -> MockTerminal {
+        MockTerminal { input: input.iter().copied().collect(), output: Vec::new() }
+    }
+}
+
+impl Terminal for MockTerminal {
+    fn read_byte(&mut self) -> u8 {
+        self.input.pop_front().expect("read past the scripted input")
+    }
+
+    fn write_byte(&mut self, byte: u8) {
+        self.output.push(byte);
+    }
+}
+
+/// A completer offering fixed command names and file names, where a trailing
+/// `/` marks a directory.
+struct MockCompleter {
+    commands: &'static [&'static str],
+    files: &'static [&'static str],
+}
+
+impl Completer for MockCompleter {
+    fn complete(&self, _word: &str, command: bool, f: &mut dyn FnMut(&str, bool)) {
+        if command {
+            self.commands.iter().for_each(|name| f(name, false));
+        } else {
+            for name in self.files {
+                match name.strip_suffix('/') {
+                    Some(dir) => f(dir, true),
+                    None => f(name, false),
+                }
+            }
+        }
+    }
+}
+
+const COMPLETER: MockCompleter = MockCompleter {
+    commands: &["cat", "cd", "clear", "hexdump"],
+    files: &["boot/", "bootcode.bin", "config.txt", "kernel8.img"],
+};
+
+/// Parses `line` with no variables set, using `out` for the arguments' text.
+fn parse<'a>(line: &'a str, out: &'a mut [u8], buf: &'a mut [&'a str]) -> Result<Command<'a>, Error> {
+    let mut storage: [Variable; 0] = [];
+    Command::parse(line, &StackVec::new(&mut storage), out, buf)
+}
+
+#[test]
+fn parse_empty() {
+    let line = String::new();
+    let mut out = [0; 64];
+    let mut buf = [""; 4];
+    assert!(matches!(parse(&line, &mut out, &mut buf), Err(Error::Empty)));
+}
+
+#[test]
+fn parse_whitespace_only() {
+    let line = String::from("  \t  ");
+    let mut out = [0; 64];
+    let mut buf = [""; 4];
+    assert!(matches!(parse(&line, &mut out, &mut buf), Err(Error::Empty)));
+}
+
+#[test]
+fn parse_args() {
+    let line = String::from("  echo  hello world ");
+    let mut out = [0; 64];
+    let mut buf = [""; 4];
+    let command = parse(&line, &mut out, &mut buf).expect("parses");
+    assert_eq!(command.path(), "echo");
+    assert_eq!(command.args.as_slice(), &["echo", "hello", "world"]);
+}
+
+#[test]
+fn parse_too_many_args() {
+    let line = String::from("a b c d");
+    let mut out = [0; 64];
+    let mut buf = [""; 4];
+    assert!(parse(&line, &mut out, &mut buf).is_ok());
+
+    let line = String::from("a b c d e");
+    let mut out = [0; 64];
+    let mut buf = [""; 4];
+    assert!(matches!(
+        parse(&line, &mut out, &mut buf),
+        Err(Error::TooManyArgs { capacity: 4, count: 5 })
+    ));
+
+    let line = String::from("a b c d e \"f g\" h");
+    let mut out = [0; 64];
+    let mut buf = [""; 2];
+    assert!(matches!(
+        parse(&line, &mut out, &mut buf),
+        Err(Error::TooManyArgs { capacity: 2, count: 7 })
+    ));
+}
+
+#[test]
+fn parse_tabs_and_spaces() {
+    let line = String::from("echo\ta \t b\t\tc");
+    let mut out = [0; 64];
+    let mut buf = [""; 8];
+    let command = parse(&line, &mut out, &mut buf).expect("parses");
+    assert_eq!(command.args.as_slice(), &["echo", "a", "b", "c"]);
+}
+
+#[test]
+fn parse_quoted() {
+    let line = String::from(r#"echo "hello world" "" x"#);
+    let mut out = [0; 64];
+    let mut buf = [""; 8];
+    let command = parse(&line, &mut out, &mut buf).expect("parses");
+    assert_eq!(command.args.as_slice(), &["echo", "hello world", "", "x"]);
+}
+
+#[test]
+fn parse_adjacent_quotes() {
+    let line = String::from(r#"echo "a b""c" d"e f"g"#);
+    let mut out = [0; 64];
+    let mut buf = [""; 8];
+    let command = parse(&line, &mut out, &mut buf).expect("parses");
+    assert_eq!(command.args.as_slice(), &["echo", "a bc", "de fg"]);
+}
+
+#[test]
+fn parse_escaped_quote() {
+    let line = String::from(r#"echo "say \"hi\"" \ "a\b""#);
+    let mut out = [0; 64];
+    let mut buf = [""; 8];
+    let command = parse(&line, &mut out, &mut buf).expect("parses");
+    assert_eq!(command.args.as_slice(), &["echo", r#"say "hi""#, r"\", r"a\b"]);
+}
+
+#[test]
+fn parse_unicode_in_quotes() {
+    let line = String::from("echo \"h\u{e9}llo w\u{f6}rld\"\u{1f600}");
+    let mut out = [0; 64];
+    let mut buf = [""; 8];
+    let command = parse(&line, &mut out, &mut buf).expect("parses");
+    assert_eq!(command.args.as_slice(), &["echo", "h\u{e9}llo w\u{f6}rld\u{1f600}"]);
+}
+
+#[test]
+fn parse_unterminated_quote() {
+    let line = String::from(r#"echo "hello"#);
+    let mut out = [0; 64];
+    let mut buf = [""; 8];
+    assert!(matches!(parse(&line, &mut out, &mut buf), Err(Error::UnterminatedQuote)));
+}
+
+#[test]
+fn parse_redirect() {
+    let line = String::from("echo hi > out.txt");
+    let mut out = [0; 64];
+    let mut buf = [""; 8];
+    let command = parse(&line, &mut out, &mut buf).expect("parses");
+    assert_eq!(command.args.as_slice(), &["echo", "hi"]);
+    assert_eq!(command.redirect, Some(Redirect { path: "out.txt", append: false }));
+
+    let line = String::from("echo a b >> \"log file\"  ");
+    let mut out = [0; 64];
+    let mut buf = [""; 8];
+    let command = parse(&line, &mut out, &mut buf).expect("parses");
+    assert_eq!(command.args.as_slice(), &["echo", "a", "b"]);
+    assert_eq!(command.redirect, Some(Redirect { path: "log file", append: true }));
+
+    let line = String::from("echo hi");
+    let mut out = [0; 64];
+    let mut buf = [""; 8];
+    assert_eq!(parse(&line, &mut out, &mut buf).expect("parses").redirect, None);
+}
+
+#[test]
+fn parse_quoted_redirect_is_an_argument() {
+    let line = String::from("echo \">\" \">>\" a>b");
+    let mut out = [0; 64];
+    let mut buf = [""; 8];
+    let command = parse(&line, &mut out, &mut buf).expect("parses");
+    assert_eq!(command.args.as_slice(), &["echo", ">", ">>", "a>b"]);
+    assert_eq!(command.redirect, None);
+}
+
+#[test]
+fn parse_bad_redirect() {
+    for input in &["echo hi >", "echo hi >>  ", "echo hi > a b", "echo > a > b"] {
+        let line = String::from(*input);
+        let mut out = [0; 64];
+        let mut buf = [""; 8];
+        assert!(matches!(parse(&line, &mut out, &mut buf), Err(Error::BadRedirect)), "{}", input);
+    }
+}
+
+/// Splits `line` into the commands of its sequence.
+fn commands(line: &str) -> Vec<String> {
+    let mut line = String::from(line);
+    let mut rest = Some(line.as_mut_str());
+    let mut commands = vec![];
+    while let Some(s) = rest {
+        let (command, tail) = next_command(s);
+        commands.push(command.to_string());
+        rest = tail;
+    }
+
+    commands
+}
+
+#[test]
+fn command_sequence() {
+    assert_eq!(commands("echo a; echo b"), ["echo a", " echo b"]);
+    assert_eq!(commands("echo a;ls"), ["echo a", "ls"]);
+    assert_eq!(commands("echo a"), ["echo a"]);
+
+    let mut line = String::from("echo a; echo b");
+    let (first, rest) = next_command(&mut line);
+    let (mut out, mut buf) = ([0; 64], [""; 4]);
+    assert_eq!(parse(first, &mut out, &mut buf).expect("parses").args.as_slice(), &["echo", "a"]);
+    let (second, rest) = next_command(rest.expect("second command"));
+    let (mut out, mut buf) = ([0; 64], [""; 4]);
+    assert_eq!(parse(second, &mut out, &mut buf).expect("parses").args.as_slice(), &["echo", "b"]);
+    assert!(rest.is_none());
+}
+
+#[test]
+fn command_sequence_empty_segments() {
+    assert_eq!(commands("echo a;"), ["echo a", ""]);
+    assert_eq!(commands("; ;echo a"), ["", " ", "echo a"]);
+
+    // Empty commands parse as `Error::Empty`, which the shell skips.
+    for command in commands("echo a; ;") {
+        let (mut out, mut buf) = ([0; 64], [""; 4]);
+        match parse(&command, &mut out, &mut buf) {
+            Ok(command) => assert_eq!(command.args.as_slice(), &["echo", "a"]),
+            Err(e) => assert!(matches!(e, Error::Empty)),
+        }
+    }
+}
+
+#[test]
+fn command_sequence_quoted_semicolon() {
+    assert_eq!(commands("echo \"a; b\"; ls"), ["echo \"a; b\"", " ls"]);
+    assert_eq!(commands("echo \"a\\\"; b\"; ls"), ["echo \"a\\\"; b\"", " ls"]);
+    assert_eq!(commands("echo \"a; b"), ["echo \"a; b"]);
+}
+
+#[test]
+fn read_line_full_buffer() {
+    let mut input = vec![b'a'; MAX_LINE_LENGTH + 1];
+    input.push(b'\r');
+
+    let mut term = MockTerminal::new(&input);
+    let mut storage = [0u8; MAX_LINE_LENGTH];
+    let mut line = StackVec::new(&mut storage);
+    let mut history_storage = [HistoryEntry::EMPTY; 1];
+    let history = StackVec::new(&mut history_storage);
+    assert!(read_line(&mut term, "> ", &mut line, &history, &COMPLETER));
+    assert!(term.input.is_empty());
+
+    assert_eq!(line.len(), MAX_LINE_LENGTH);
+    assert_eq!(term.output.iter().filter(|&&byte| byte == 7).count(), 1);
+    assert_eq!(term.output.iter().filter(|&&byte| byte == b'a').count(), MAX_LINE_LENGTH);
+
+    let command_string = std::str::from_utf8_mut(line.into_slice()).expect("ascii");
+    let (mut out, mut buf) = ([0; MAX_LINE_LENGTH], [""; 4]);
+    let command = parse(command_string, &mut out, &mut buf).expect("parses");
+    assert_eq!(command.path().len(), MAX_LINE_LENGTH);
+}
+
+/// Feeds `input` to `read_line` over a line buffer that fits `capacity`
+/// bytes, returning the line that was read and everything echoed.
+fn read_input_echo(input: &[u8], capacity: usize) -> (Vec<u8>, Vec<u8>) {
+    let mut term = MockTerminal::new(input);
+    let mut storage = vec![0u8; capacity];
+    let mut line = StackVec::new(&mut storage);
+    let mut history_storage = [HistoryEntry::EMPTY; 1];
+    let history = StackVec::new(&mut history_storage);
+    assert!(read_line(&mut term, "> ", &mut line, &history, &COMPLETER));
+    assert!(term.input.is_empty());
+    (line.as_slice().to_vec(), term.output)
+}
+
+/// Feeds `input` to `read_line` over a line buffer that fits `capacity`
+/// bytes, returning the line that was read.
+fn read_input(input: &[u8], capacity: usize) -> Vec<u8> {
+    read_input_echo(input, capacity).0
+}
+
+#[test]
+fn read_line_utf8() {
+    assert_eq!(read_input("a\u{e9}\r".as_bytes(), 16), "a\u{e9}".as_bytes());
+    assert_eq!(read_input("\u{1f600}b\r".as_bytes(), 16), "\u{1f600}b".as_bytes());
+}
+
+#[test]
+fn read_line_utf8_backspace() {
+    assert_eq!(read_input("a\u{e9}\x7f\r".as_bytes(), 16), b"a");
+    assert_eq!(read_input("a\u{1f600}\x7f\r".as_bytes(), 16), b"a");
+    assert_eq!(read_input("\u{e9}\u{1f600}\x7f\x7f\x7f\r".as_bytes(), 16), b"");
+}
+
+#[test]
+fn read_line_utf8_cursor() {
+    let input = "\u{e9}\u{1f600}\x1b[D\x1b[Dx\x1b[C\x7f\r";
+    assert_eq!(read_input(input.as_bytes(), 16), "x\u{1f600}".as_bytes());
+}
+
+#[test]
+fn read_line_ignores_unknown_escapes() {
+    assert_eq!(read_input(b"ab\x1b[3~c\r", 16), b"abc");
+    assert_eq!(read_input(b"ab\x1b[1;5Dc\r", 16), b"abc");
+    assert_eq!(read_input(b"ab\x1b[Zc\x1b[D\x1b[Dx\r", 16), b"axbc");
+}
+
+#[test]
+fn read_line_invalid_utf8() {
+    assert_eq!(read_input(b"a\xffb\xc3\x28\r", 16), b"ab");
+}
+
+#[test]
+fn read_line_utf8_full_buffer() {
+    assert_eq!(read_input("ab\u{1f600}\r".as_bytes(), 4), b"ab");
+}
+
+#[test]
+fn hexdump_row() {
+    let row = HexRow { offset: 0x10, bytes: b"Hello, world!\x00\x7f\xff" };
+    assert_eq!(
+        row.to_string(),
+        "00000010  48 65 6c 6c 6f 2c 20 77  6f 72 6c 64 21 00 7f ff  |Hello, world!...|"
+    );
+
+    let row = HexRow { offset: 0x1234, bytes: b"abc" };
+    assert_eq!(
+        row.to_string(),
+        "00001234  61 62 63                                          |abc|"
+    );
+}
+
+#[test]
+fn sleep_arguments() {
+    assert_eq!(sleep_duration(&["250"]), Ok(Duration::from_millis(250)));
+    assert_eq!(sleep_duration(&["0"]), Ok(Duration::from_millis(0)));
+    assert!(sleep_duration(&[]).is_err());
+    assert!(sleep_duration(&["1s"]).is_err());
+    assert!(sleep_duration(&["-5"]).is_err());
+    assert!(sleep_duration(&["1", "2"]).is_err());
+}
+
+#[test]
+fn echo_flags() {
+    let echo = |args: &[&str]| Echo::new(args).to_string();
+    assert_eq!(echo(&[]), "\n");
+    assert_eq!(echo(&["a", "b"]), "a b\n");
+    assert_eq!(echo(&["-n", "a", "b"]), "a b");
+    assert_eq!(echo(&["-n"]), "");
+
+    // Flags are only recognized at the start.
+    assert_eq!(echo(&["a", "-n"]), "a -n\n");
+    assert_eq!(echo(&["-n", "-n"]), "-n");
+    assert_eq!(echo(&["-x", "a"]), "-x a\n");
+    assert_eq!(echo(&["-", "a"]), "- a\n");
+}
+
+#[test]
+fn echo_escapes() {
+    let echo = |args: &[&str]| Echo::new(args).to_string();
+    assert_eq!(echo(&["a\\nb"]), "a\\nb\n");
+    assert_eq!(echo(&["-e", "a\\nb\\tc"]), "a\nb\tc\n");
+    assert_eq!(echo(&["-e", "\\\\n", "\\q", "end\\"]), "\\n \\q end\\\n");
+
+    // Flags can be combined in either order.
+    assert_eq!(echo(&["-ne", "a\\n"]), "a\n");
+    assert_eq!(echo(&["-en", "a\\n"]), "a\n");
+    assert_eq!(echo(&["-n", "-e", "a\\n"]), "-e a\\n");
+}
+
+#[test]
+fn complete_unique() {
+    assert_eq!(read_input(b"he\t\r", 32), b"hexdump ");
+    assert_eq!(read_input(b"cat con\t\r", 32), b"cat config.txt ");
+    assert_eq!(read_input(b"cat /b/k\t\r", 32), b"cat /b/kernel8.img ");
+    assert_eq!(read_input(b"cd bo\t\r", 32), b"cd boot");
+    assert_eq!(read_input(b"cd boot\t\r", 32), b"cd boot");
+}
+
+#[test]
+fn complete_common_prefix() {
+    assert_eq!(read_input(b"cl\t\r", 32), b"clear ");
+    assert_eq!(read_input(b"c\t\r", 32), b"c");
+    assert_eq!(read_input(b"cat b\t\r", 32), b"cat boot");
+    assert_eq!(read_input(b"cat b\t\t\r", 32), b"cat boot");
+    assert_eq!(read_input(b"cat boot\tc\t\r", 32), b"cat bootcode.bin ");
+}
+
+#[test]
+fn complete_lists_candidates() {
+    let (line, output) = read_input_echo(b"cat boot\t\r", 32);
+    assert_eq!(line, b"cat boot");
+    let output = String::from_utf8(output).expect("ascii");
+    assert!(output.ends_with("\nboot/  bootcode.bin  \n> cat boot\n"));
+
+    // the terminal cursor is put back where it was in the line
+    let (line, output) = read_input_echo(b"cat boot x\x1b[D\x1b[D\t\r", 32);
+    assert_eq!(line, b"cat boot x");
+    let output = String::from_utf8(output).expect("ascii");
+    assert!(output.ends_with("\n> cat boot x\x08\x08\n"));
+}
+
+#[test]
+fn complete_in_the_middle() {
+    assert_eq!(read_input(b"he x\x1b[D\x1b[D\t\r", 32), b"hexdump  x");
+}
+
+#[test]
+fn complete_no_match_rings_bell() {
+    let (line, output) = read_input_echo(b"xyz\t\r", 32);
+    assert_eq!(line, b"xyz");
+    assert_eq!(output.iter().filter(|&&byte| byte == 7).count(), 1);
+
+    // a completion that doesn't fit rings the bell too
+    let (line, output) = read_input_echo(b"hex\t\r", 6);
+    assert_eq!(line, b"hex");
+    assert_eq!(output.iter().filter(|&&byte| byte == 7).count(), 1);
+}
+
+#[test]
+fn uptime_display() {
+    let uptime = |secs| Uptime(Duration::from_secs(secs)).to_string();
+    assert_eq!(uptime(0), "0s");
+    assert_eq!(uptime(59), "59s");
+    assert_eq!(uptime(60), "1m 0s");
+    assert_eq!(uptime(3605), "1h 0m 5s");
+    assert_eq!(uptime(86400), "1d 0h 0m 0s");
+    assert_eq!(uptime(2 * 86400 + 3 * 3600 + 4 * 60 + 5), "2d 3h 4m 5s");
+    assert_eq!(Uptime(Duration::from_millis(1999)).to_string(), "1s");
+}
+
+#[test]
+fn clock_format() {
+    let clock = |secs| Clock(Duration::from_secs(secs)).to_string();
+    assert_eq!(clock(0), "00:00:00");
+    assert_eq!(clock(59), "00:00:59");
+    assert_eq!(clock(61), "00:01:01");
+    assert_eq!(clock(3600 + 2 * 60 + 3), "01:02:03");
+    assert_eq!(clock(86399), "23:59:59");
+    assert_eq!(clock(100 * 3600), "100:00:00");
+    assert_eq!(Clock(Duration::from_millis(1999)).to_string(), "00:00:01");
+}
+
+#[test]
+fn prompt_formatting() {
+    let prompt = |custom: &str, cwd: &str| {
+        let mut out = String::new();
+        write_prompt(&mut out, ">", custom, Path::new(cwd)).expect("formats");
+        out
+    };
+
+    assert_eq!(prompt("", "/boot"), "> ");
+    assert_eq!(prompt("{cwd} $ ", "/boot"), "/boot $ ");
+    assert_eq!(prompt("[{cwd}|{cwd}] ", "/"), "[/|/] ");
+    assert_eq!(prompt("pi% ", "/boot"), "pi% ");
+    assert_eq!(prompt("{cw}{cwd", "/"), "{cw}{cwd");
+}
+
+#[test]
+fn variables_set_and_unset() {
+    let mut storage = [Variable::EMPTY; 2];
+    let mut vars = StackVec::new(&mut storage);
+    set_variable(&mut vars, "NAME", "pi").expect("set");
+    set_variable(&mut vars, "_dir2", "/boot").expect("set");
+    assert_eq!(lookup(&vars, "NAME"), Some("pi"));
+    assert_eq!(lookup(&vars, "_dir2"), Some("/boot"));
+    assert_eq!(lookup(&vars, "name"), None);
+
+    // Setting a variable again replaces its value.
+    set_variable(&mut vars, "NAME", "").expect("set");
+    assert_eq!(lookup(&vars, "NAME"), Some(""));
+    assert_eq!(vars.len(), 2);
+
+    assert!(set_variable(&mut vars, "MORE", "x").is_err());
+    assert!(set_variable(&mut vars, "2X", "x").is_err());
+    assert!(set_variable(&mut vars, "A-B", "x").is_err());
+    assert!(set_variable(&mut vars, "NAME", &"x".repeat(VARIABLE_VALUE_CAPACITY + 1)).is_err());
+
+    assert!(unset_variable(&mut vars, "NAME"));
+    assert!(!unset_variable(&mut vars, "NAME"));
+    assert_eq!(lookup(&vars, "NAME"), None);
+    assert_eq!(lookup(&vars, "_dir2"), Some("/boot"));
+    set_variable(&mut vars, "MORE", "x").expect("room after unset");
+}
+
+/// Parses `s` with `NAME=pi`, `DIR=/boot` and `TEXT=a "b" > $NAME` set,
+/// returning its arguments once expanded into `capacity` bytes.
+fn expanded(s: &str, capacity: usize) -> Result<Vec<String>, Error> {
+    let mut storage = [Variable::EMPTY; 3];
+    let mut vars = StackVec::new(&mut storage);
+    set_variable(&mut vars, "NAME", "pi").expect("set");
+    set_variable(&mut vars, "DIR", "/boot").expect("set");
+    set_variable(&mut vars, "TEXT", "a \"b\" > $NAME").expect("set");
+
+    let (mut out, mut buf) = (vec![0u8; capacity], [""; 8]);
+    let command = Command::parse(s, &vars, &mut out, &mut buf)?;
+    assert_eq!(command.redirect, None);
+    Ok(command.args.iter().map(|arg| arg.to_string()).collect())
+}
+
+#[test]
+fn variables_expand() {
+    assert_eq!(expanded("echo $NAME", 64).unwrap(), ["echo", "pi"]);
+    assert_eq!(expanded("ls $DIR/x ${NAME}s", 64).unwrap(), ["ls", "/boot/x", "pis"]);
+    assert_eq!(expanded("echo $NAMEs $UNSET.", 64).unwrap(), ["echo", "."]);
+    assert_eq!(expanded("echo $ $1 ${} ${NAME $", 64).unwrap(), ["echo", "$", "$1", "${}", "${NAME", "$"]);
+    assert_eq!(expanded("\u{e9}$NAME\u{e9}", 64).unwrap(), ["\u{e9}pi\u{e9}"]);
+    assert!(matches!(expanded("echo $DIR", 8), Err(Error::TooLong)));
+}
+
+#[test]
+fn variables_expand_in_quotes() {
+    assert_eq!(expanded("echo \"$NAME and ${DIR}\" $NAME", 64).unwrap(), ["echo", "pi and /boot", "pi"]);
+    assert_eq!(expanded("echo \"$UNSET\" \"$\"NAME", 64).unwrap(), ["echo", "", "$NAME"]);
+}
+
+#[test]
+fn variables_expand_literally() {
+    // A value is a single argument, and its quotes, `>` and `$` are kept.
+    assert_eq!(expanded("echo $TEXT", 64).unwrap(), ["echo", "a \"b\" > $NAME"]);
+    assert_eq!(expanded("echo x$TEXT\"$TEXT\"", 64).unwrap(), ["echo", "xa \"b\" > $NAMEa \"b\" > $NAME"]);
+
+    let mut storage = [Variable::EMPTY; 1];
+    let mut vars = StackVec::new(&mut storage);
+    set_variable(&mut vars, "FILE", "out.txt").expect("set");
+    let (mut out, mut buf) = ([0; 64], [""; 4]);
+    let command = Command::parse("echo hi > $FILE", &vars, &mut out, &mut buf).expect("parses");
+    assert_eq!(command.redirect, Some(Redirect { path: "out.txt", append: false }));
+}
+
+#[derive(Clone, Copy)]
+struct MockTimestamp(usize, u8, u8, u8, u8, u8);
+
+impl Timestamp for MockTimestamp {
+    fn year(&self) -> usize {
+        self.0
+    }
+
+    fn month(&self) -> u8 {
+        self.1
+    }
+
+    fn day(&self) -> u8 {
+        self.2
+    }
+
+    fn hour(&self) -> u8 {
+        self.3
+    }
+
+    fn minute(&self) -> u8 {
+        self.4
+    }
+
+    fn second(&self) -> u8 {
+        self.5
+    }
+}
+
+struct MockMetadata {
+    read_only: bool,
+    hidden: bool,
+}
+
+impl Metadata for MockMetadata {
+    type Timestamp = MockTimestamp;
+
+    fn read_only(&self) -> bool {
+        self.read_only
+    }
+
+    fn hidden(&self) -> bool {
+        self.hidden
+    }
+
+    fn created(&self) -> MockTimestamp {
+        MockTimestamp(2019, 3, 14, 15, 9, 26)
+    }
+
+    fn accessed(&self) -> MockTimestamp {
+        MockTimestamp(2020, 1, 1, 0, 0, 0)
+    }
+
+    fn modified(&self) -> MockTimestamp {
+        MockTimestamp(2019, 12, 31, 23, 59, 58)
+    }
+}
+
+#[test]
+fn stat_arguments() {
+    assert_eq!(stat_path(&["/boot/config.txt"]), Ok("/boot/config.txt"));
+    assert!(stat_path(&[]).is_err());
+    assert!(stat_path(&["a", "b"]).is_err());
+}
+
+#[test]
+fn stat_display() {
+    assert_eq!(IsoTime(MockTimestamp(1980, 1, 2, 3, 4, 5)).to_string(), "1980-01-02 03:04:05");
+
+    let metadata = MockMetadata { read_only: true, hidden: false };
+    assert_eq!(
+        Stat { size: Some(1234), metadata: &metadata }.to_string(),
+        "type      file\n\
+         size      1234 bytes\n\
+         flags     read-only\n\
+         created   2019-03-14 15:09:26\n\
+         modified  2019-12-31 23:59:58\n\
+         accessed  2020-01-01 00:00:00"
+    );
+
+    let metadata = MockMetadata { read_only: false, hidden: true };
+    let report = Stat { size: None, metadata: &metadata }.to_string();
+    assert!(report.starts_with("type      directory\nflags     hidden\n"));
+}
+
+/// Returns the numbered lines of `input` that `grep_lines` matches with
+/// `pattern`.
+fn grep_matches(input: &[u8], pattern: &str) -> Vec<(usize, String)> {
+    let mut matches = vec![];
+    grep_lines(input, pattern.as_bytes(), &mut |number, line| {
+        matches.push((number, String::from_utf8_lossy(line).into_owned()));
+    })
+    .expect("reads");
+    matches
+}
+
+const GREP_FIXTURE: &[u8] = b"boot: ok\nuart: ready\n\nboot: done\nlast line without newline";
+
+#[test]
+fn grep_matching() {
+    let lines = |pairs: &[(usize, &str)]| -> Vec<(usize, String)> {
+        pairs.iter().map(|&(n, line)| (n, line.to_string())).collect()
+    };
+
+    assert_eq!(grep_matches(GREP_FIXTURE, "boot"), lines(&[(1, "boot: ok"), (4, "boot: done")]));
+    assert_eq!(grep_matches(GREP_FIXTURE, "ready"), lines(&[(2, "uart: ready")]));
+    assert_eq!(grep_matches(GREP_FIXTURE, "newline"), lines(&[(5, "last line without newline")]));
+    assert_eq!(grep_matches(GREP_FIXTURE, "missing"), lines(&[]));
+    assert_eq!(grep_matches(GREP_FIXTURE, "").len(), 5);
+    assert_eq!(grep_matches(b"a\n\n", "").len(), 2);
+    assert_eq!(grep_matches(b"", ""), lines(&[]));
+}
+
+#[test]
+fn grep_long_lines() {
+    let mut input = vec![b'x'; GREP_LINE_CAPACITY + 10];
+    input.extend_from_slice(b"needle\nshort needle\n");
+    let matches = grep_matches(&input, "needle");
+    assert_eq!(matches.len(), 2);
+    assert_eq!(matches[0].0, 1);
+    assert!(matches[0].1.ends_with("needle"));
+    assert_eq!(matches[1], (2, "short needle".to_string()));
+}
+
+/// Returns the counts of `input` fed to `Counts` in chunks of `chunk_size`.
+fn counts(input: &[u8], chunk_size: usize) -> (usize, usize, usize) {
+    let mut counts = Counts::default();
+    input.chunks(chunk_size).for_each(|chunk| counts.feed(chunk));
+    (counts.lines, counts.words, counts.bytes)
+}
+
+#[test]
+fn wc_counts() {
+    const FIXTURE: &[u8] = b"hello world\n  two\tspaced  words\n\nlast";
+
+    for chunk_size in 1..=FIXTURE.len() {
+        assert_eq!(counts(FIXTURE, chunk_size), (3, 6, FIXTURE.len()), "chunk size {}", chunk_size);
+    }
+
+    assert_eq!(counts(b"", 4), (0, 0, 0));
+    assert_eq!(counts(b"\n", 4), (1, 0, 1));
+    assert_eq!(counts(b"word", 4), (0, 1, 4));
+    assert_eq!(counts(b"word\n", 4), (1, 1, 5));
+    assert_eq!(counts(b"word\n\n", 4), (2, 1, 6));
+    assert_eq!(counts(b"   \n  ", 2), (1, 0, 6));
+    assert_eq!(counts(b"a b\r\nc", 1), (1, 3, 6));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn read_line_spills_to_heap() {
+    use super::SpillLine;
+
+    let read = |input: &[u8], capacity: usize| {
+        let mut term = MockTerminal::new(input);
+        let mut storage = vec![0u8; capacity];
+        let mut line = SpillLine::new(&mut storage);
+        let mut history_storage = [HistoryEntry::EMPTY; 1];
+        let history = StackVec::new(&mut history_storage);
+        assert!(read_line(&mut term, "> ", &mut line, &history, &COMPLETER));
+        assert!(!term.output.contains(&7));
+        (matches!(line, SpillLine::Heap(_)), line.to_vec())
+    };
+
+    assert_eq!(read(b"abcd\r", 4), (false, b"abcd".to_vec()));
+    assert_eq!(read(b"abcde\r", 4), (true, b"abcde".to_vec()));
+    assert_eq!(read(b"abcd\x1b[D\x1b[Dxy\r", 4), (true, b"abxycd".to_vec()));
+    assert_eq!(read(b"abcde\x08\x08\x08f\r", 4), (true, b"abf".to_vec()));
+
+    let mut input = vec![b'a'; MAX_LINE_LENGTH * 3];
+    input.push(b'\r');
+    let (spilled, line) = read(&input, MAX_LINE_LENGTH);
+    assert!(spilled);
+    assert_eq!(line.len(), MAX_LINE_LENGTH * 3);
+}
+
+#[test]
+fn find_parse_args() {
+    let args = |start, name, kind| Ok(FindArgs { start, name, kind });
+
+    assert_eq!(find_args(&["/", "kernel8.img"]), args("/", "kernel8.img", None));
+    assert_eq!(find_args(&["-type", "f", ".", "a"]), args(".", "a", Some(EntryKind::File)));
+    assert_eq!(find_args(&[".", "-type", "d", "a"]), args(".", "a", Some(EntryKind::Dir)));
+    assert_eq!(find_args(&[".", "a", "-type", "d"]), args(".", "a", Some(EntryKind::Dir)));
+    assert_eq!(find_args(&["-type", "d", "-type", "f", "/", "a"]), args("/", "a", Some(EntryKind::File)));
+
+    assert!(find_args(&[]).is_err());
+    assert!(find_args(&["/"]).is_err());
+    assert!(find_args(&["/", "a", "b"]).is_err());
+    assert!(find_args(&["/", "a", "-type"]).is_err());
+    assert!(find_args(&["/", "a", "-type", "x"]).is_err());
+}
+
+/// A directory of a mock tree: its path and its entries, with their sizes, or
+/// `None` for subdirectories.
+type MockDir = (&'static str, &'static [(&'static str, Option<u64>)]);
+
+/// The directories of a mock tree for `disk_usage`.
+const TREE: &[MockDir] = &[
+    ("/", &[("a.txt", Some(100)), ("docs", None), ("src", None), ("empty", None)]),
+    ("/docs", &[("x", Some(1000)), ("deep", None), ("y", Some(24))]),
+    ("/docs/deep", &[("z", Some(5000))]),
+    ("/src", &[("m", Some(10))]),
+    ("/empty", &[]),
+];
+
+/// Lists the directory `dir` of `TREE` for `disk_usage`.
+fn list_tree(dir: &Path, f: &mut dyn FnMut(&str, Option<u64>)) -> std::io::Result<()> {
+    let dir = dir.to_str().expect("UTF-8 path");
+    let entries = TREE.iter().find(|(path, _)| *path == dir).map(|(_, entries)| *entries);
+    let entries = entries.ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, dir))?;
+    entries.iter().for_each(|&(name, size)| f(name, size));
+    Ok(())
+}
+
+/// Runs `disk_usage` from `start` over `TREE`, returning the reported
+/// subdirectories in order and the total.
+fn usage(start: &str) -> std::io::Result<(Vec<(String, u64)>, u64)> {
+    let mut reported = vec![];
+    let total = disk_usage(Path::new(start), &mut list_tree, &mut |path, size| {
+        reported.push((path.to_str().expect("UTF-8 path").to_string(), size));
+    })?;
+
+    Ok((reported, total))
+}
+
+#[test]
+fn du_sums_tree() {
+    let (mut reported, total) = usage("/").expect("sized");
+    reported.sort();
+    assert_eq!(reported, vec![
+        ("/docs".to_string(), 6024),
+        ("/empty".to_string(), 0),
+        ("/src".to_string(), 10),
+    ]);
+    assert_eq!(total, 6134);
+
+    assert_eq!(usage("/docs").expect("sized"), (vec![("/docs/deep".to_string(), 5000)], 6024));
+    assert_eq!(usage("/empty").expect("sized"), (vec![], 0));
+    assert_eq!(usage("/missing").expect_err("not found").kind(), std::io::ErrorKind::NotFound);
+}
+
+#[test]
+fn du_skips_directories_that_dont_fit() {
+    // Only `/docs` fits on the stack; `/src` and `/empty` are skipped.
+    let mut storage: [(PathBuf, bool); 1] = Default::default();
+    let mut reported = vec![];
+    let total = walk_usage(&mut StackVec::new(&mut storage), Path::new("/"), &mut list_tree, &mut |path, size| {
+        reported.push((path.to_str().expect("UTF-8 path").to_string(), size));
+    });
+
+    assert_eq!(total.expect("sized"), 6124);
+    assert_eq!(reported, vec![("/docs".to_string(), 6024)]);
+}
+
+/// Reads lines from `input`, followed by an `end` line, with `read_line` over
+/// a terminal normalizing line endings, and splits each into commands,
+/// returning the commands' paths up to `end`.
+fn read_commands(input: &[u8]) -> Vec<String> {
+    let mut input = input.to_vec();
+    input.extend_from_slice(b"end\r");
+    let mut term = MockTerminal::new(&input);
+    let mut term = LineEndings::new(&mut term);
+    let mut history_storage = [HistoryEntry::EMPTY; 1];
+    let history = StackVec::new(&mut history_storage);
+    let mut paths = vec![];
+    loop {
+        let mut storage = [0u8; 64];
+        let mut line = StackVec::new(&mut storage);
+        assert!(read_line(&mut term, "> ", &mut line, &history, &COMPLETER));
+
+        let (mut out, mut buf) = ([0; 64], [""; 4]);
+        match parse(line.as_str().expect("UTF-8"), &mut out, &mut buf) {
+            Ok(command) if command.path() == "end" => return paths,
+            Ok(command) => paths.push(command.path().to_string()),
+            Err(Error::Empty) => paths.push(String::new()),
+            Err(e) => panic!("unexpected error {:?}", e),
+        }
+    }
+}
+
+#[test]
+fn read_line_endings() {
+    assert_eq!(read_commands(b"cmd\r\n"), ["cmd"]);
+    assert_eq!(read_commands(b"cmd\n\r"), ["cmd"]);
+    assert_eq!(read_commands(b"cmd\r"), ["cmd"]);
+    assert_eq!(read_commands(b"cmd\n"), ["cmd"]);
+    assert_eq!(read_commands(b"a\r\nb\r\n"), ["a", "b"]);
+    assert_eq!(read_commands(b"a\nb\nc\n"), ["a", "b", "c"]);
+
+    // Only the second byte of a break pair is swallowed, so blank lines
+    // still come through.
+    assert_eq!(read_commands(b"a\r\n\r\nb\r\n"), ["a", "", "b"]);
+    assert_eq!(read_commands(b"a\r\rb\r"), ["a", "", "b"]);
+    assert_eq!(read_commands(b"a\n\nb\n"), ["a", "", "b"]);
+}
+
+/// Pages `input` with `page` over a terminal pressing `keys`, returning what
+/// was shown with the prompts left out.
+fn page_input(input: &[u8], rows: usize, keys: &[u8]) -> String {
+    let mut term = MockTerminal::new(keys);
+    page(input, &mut term, rows).expect("reads");
+    assert!(term.input.is_empty());
+
+    let output = String::from_utf8(term.output).expect("UTF-8");
+    output.replace("--More--\r        \r", "")
+}
+
+#[test]
+fn less_pages() {
+    const LINES: &[u8] = b"1\n2\n3\n4\n5\n6\n7\n8\n";
+
+    // A file that fits on a page is shown without a prompt.
+    assert_eq!(page_input(LINES, 8, b""), "1\n2\n3\n4\n5\n6\n7\n8\n");
+    assert_eq!(page_input(b"no newline", 1, b""), "no newline");
+
+    // Space shows a page, Enter a line.
+    assert_eq!(page_input(LINES, 3, b" \r\n"), "1\n2\n3\n4\n5\n6\n7\n8\n");
+    assert_eq!(page_input(LINES, 3, b"\rq"), "1\n2\n3\n4\n");
+    assert_eq!(page_input(LINES, 2, b"q"), "1\n2\n");
+
+    // Other keys ring the bell and prompt again.
+    assert_eq!(page_input(LINES, 4, b"x "), "1\n2\n3\n4\n\x075\n6\n7\n8\n");
+
+    // The quit prompt is replaced with blanks.
+    let mut term = MockTerminal::new(b"q");
+    page(LINES, &mut term, 7).expect("reads");
+    assert!(term.output.ends_with(b"7\n--More--\r        \r"));
+}
+
+/// Returns the lines saved in `history`.
+fn history_lines<'h>(history: &'h StackVec<HistoryEntry>) -> Vec<&'h str> {
+    history.iter().map(|entry| std::str::from_utf8(entry.as_bytes()).unwrap()).collect()
+}
+
+#[test]
+fn history_events() {
+    assert_eq!(history_event(b"!1"), Some(1));
+    assert_eq!(history_event(b"  !12 "), Some(12));
+    assert_eq!(history_event(b"!0"), Some(0));
+
+    assert_eq!(history_event(b"!"), None);
+    assert_eq!(history_event(b"!ls"), None);
+    assert_eq!(history_event(b"!-1"), None);
+    assert_eq!(history_event(b"!1 2"), None);
+    assert_eq!(history_event(b"echo !1"), None);
+    assert_eq!(history_event(b"!99999999999999999999999"), None);
+}
+
+#[test]
+fn history_overflow() {
+    let mut history_storage = [HistoryEntry::EMPTY; 3];
+    let mut history = StackVec::new(&mut history_storage);
+    for line in ["ls", "", "pwd", "pwd", "cd /"] {
+        remember(&mut history, line.as_bytes());
+    }
+    assert_eq!(history_lines(&history), ["ls", "pwd", "cd /"]);
+
+    // Once full, the oldest entry makes room and the numbers shift down.
+    remember(&mut history, b"echo");
+    assert_eq!(history_lines(&history), ["pwd", "cd /", "echo"]);
+    remember(&mut history, &[b'x'; MAX_LINE_LENGTH + 1]);
+    assert_eq!(history.len(), 3);
+}
+
+#[test]
+fn history_file() {
+    let mut long = vec![b'x'; MAX_LINE_LENGTH + 1];
+    long.extend_from_slice(b"\nls\n\npwd\ncd /\necho hi");
+
+    let mut history_storage = [HistoryEntry::EMPTY; 3];
+    let mut history = StackVec::new(&mut history_storage);
+    load_history(&long[..], &mut history).expect("reads");
+    assert_eq!(history_lines(&history), ["pwd", "cd /", "echo hi"]);
+
+    let mut saved = vec![];
+    save_history(&mut saved, &history).expect("writes");
+    assert_eq!(saved, b"pwd\ncd /\necho hi\n");
+
+    let mut reloaded_storage = [HistoryEntry::EMPTY; 3];
+    let mut reloaded = StackVec::new(&mut reloaded_storage);
+    load_history(&saved[..], &mut reloaded).expect("reads");
+    assert_eq!(history_lines(&reloaded), history_lines(&history));
+}
+
+#[test]
+fn which_lookup() {
+    assert_eq!(describe("ls"), Some("list the entries of a directory"));
+    assert_eq!(describe("which"), Some("tell whether names are built-in commands"));
+    assert!(COMMANDS.iter().all(|(name, description)| describe(name) == Some(*description)));
+
+    assert_eq!(describe("frobnicate"), None);
+    assert_eq!(describe("LS"), None);
+    assert_eq!(describe(""), None);
+}
@@ -0,0 +1,34 @@
+mod atag;
+mod raw;
+
+pub use self::atag::*;
+
+/// The address at which the firmware loads the ATAGS.
+const ATAG_BASE: usize = 0x100;
+
+/// An iterator over the ATAGS on this system.
+pub struct Atags {
+    ptr: Option<&'static raw::Atag>,
+}
+
+impl Atags {
+    /// Returns an instance of `Atags`, an iterator over ATAGS on this system.
+    /// The iterator is empty if no well-formed ATAG list, which always starts
+    /// with a CORE tag, is found at `ATAG_BASE`.
+    pub fn get() -> Atags {
+        let first = unsafe { &*(ATAG_BASE as *const raw::Atag) };
+        Atags {
+            ptr: if first.tag == raw::Atag::CORE { Some(first) } else { None },
+        }
+    }
+}
+
+impl Iterator for Atags {
+    type Item = Atag;
+
+    fn next(&mut self) -> Option<Atag> {
+        let atag = self.ptr.filter(|atag| atag.tag != raw::Atag::NONE)?;
+        self.ptr = atag.next();
+        Some(Atag::from(atag))
+    }
+}
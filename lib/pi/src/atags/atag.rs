@@ -0,0 +1,64 @@
+use crate::atags::raw;
+
+pub use crate::atags::raw::{Core, Mem};
+
+/// An ATAG.
+#[derive(Debug, Copy, Clone)]
+pub enum Atag {
+    Core(raw::Core),
+    Mem(raw::Mem),
+    Cmd(&'static str),
+    Unknown(u32),
+    None,
+}
+
+impl Atag {
+    /// Returns `Some` if this is a `Core` ATAG. Otherwise returns `None`.
+    pub fn core(self) -> Option<Core> {
+        match self {
+            Atag::Core(core) => Some(core),
+            _ => None,
+        }
+    }
+
+    /// Returns `Some` if this is a `Mem` ATAG. Otherwise returns `None`.
+    pub fn mem(self) -> Option<Mem> {
+        match self {
+            Atag::Mem(mem) => Some(mem),
+            _ => None,
+        }
+    }
+
+    /// Returns `Some` with the command line string if this is a `Cmd` ATAG.
+    /// Otherwise returns `None`.
+    pub fn cmd(self) -> Option<&'static str> {
+        match self {
+            Atag::Cmd(cmd) => Some(cmd),
+            _ => None,
+        }
+    }
+}
+
+impl From<&'static raw::Atag> for Atag {
+    fn from(atag: &'static raw::Atag) -> Atag {
+        unsafe {
+            match atag.tag {
+                raw::Atag::CORE => Atag::Core(atag.kind.core),
+                raw::Atag::MEM => Atag::Mem(atag.kind.mem),
+                raw::Atag::CMDLINE => {
+                    // The command line is a NUL-terminated string.
+                    let start = &atag.kind.cmd.cmd as *const u8;
+                    let mut len = 0;
+                    while *start.add(len) != 0 {
+                        len += 1;
+                    }
+
+                    let bytes = core::slice::from_raw_parts(start, len);
+                    Atag::Cmd(core::str::from_utf8(bytes).unwrap_or(""))
+                }
+                raw::Atag::NONE => Atag::None,
+                id => Atag::Unknown(id),
+            }
+        }
+    }
+}
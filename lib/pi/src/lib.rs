@@ -5,7 +5,9 @@
 #![feature(never_type)]
 #![no_std]
 
+pub mod atags;
 pub mod common;
 pub mod gpio;
+pub mod pm;
 pub mod timer;
 pub mod uart;
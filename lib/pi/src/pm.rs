@@ -0,0 +1,38 @@
+use crate::common::IO_BASE;
+
+use volatile::prelude::*;
+use volatile::Volatile;
+
+/// The base address for the power management watchdog registers.
+const PM_REG_BASE: usize = IO_BASE + 0x10001c;
+
+/// Value that must be present in the top byte of every write to a power
+/// management register for the write to take effect.
+const PM_PASSWORD: u32 = 0x5a000000;
+
+/// Mask of the `WRCFG` field of `RSTC`.
+const PM_RSTC_WRCFG_MASK: u32 = 0x30;
+
+/// `WRCFG` value requesting a full reset when the watchdog expires.
+const PM_RSTC_WRCFG_FULL_RESET: u32 = 0x20;
+
+/// Watchdog timeout, in ticks of roughly 16us, used by `reset`.
+const RESET_TIMEOUT_TICKS: u32 = 10;
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct Registers {
+    RSTC: Volatile<u32>,
+    RSTS: Volatile<u32>,
+    WDOG: Volatile<u32>,
+}
+
+/// Resets the board by arming the watchdog with a short timeout. The reset
+/// takes place a fraction of a millisecond after this function returns; if
+/// execution continues well past that, the reset didn't happen.
+pub fn reset() {
+    let registers = unsafe { &mut *(PM_REG_BASE as *mut Registers) };
+    let rstc = registers.RSTC.read() & !PM_RSTC_WRCFG_MASK;
+    registers.WDOG.write(PM_PASSWORD | RESET_TIMEOUT_TICKS);
+    registers.RSTC.write(PM_PASSWORD | rstc | PM_RSTC_WRCFG_FULL_RESET);
+}
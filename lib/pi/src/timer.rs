@@ -32,17 +32,26 @@ impl Timer {
     /// Reads the system timer's counter and returns Duration.
     /// `CLO` and `CHI` together can represent the number of elapsed microseconds.
     pub fn read(&self) -> Duration {
-        unimplemented!()
+        // The two halves can't be read atomically, so re-read CHI to catch
+        // CLO wrapping around between the two reads.
+        loop {
+            let hi = self.registers.CHI.read();
+            let lo = self.registers.CLO.read();
+            if self.registers.CHI.read() == hi {
+                return Duration::from_micros(((hi as u64) << 32) | lo as u64);
+            }
+        }
     }
 }
 
 /// Returns current time.
 pub fn current_time() -> Duration {
-    unimplemented!()
+    Timer::new().read()
 }
 
 /// Spins until `t` duration have passed.
 pub fn spin_sleep(t: Duration) {
-    unimplemented!()
+    let end = current_time() + t;
+    while current_time() < end {}
 }
 
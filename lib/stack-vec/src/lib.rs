@@ -1,9 +1,35 @@
+//! Fixed-capacity vectors backed by caller-supplied storage: [`StackVec`]
+//! over a slice of values, [`RingVec`] for FIFO use, and [`UninitStackVec`]
+//! over uninitialized storage.
+//!
+//! # Moving elements out
+//!
+//! The storage of a [`StackVec`] or [`RingVec`] belongs to the caller and drops
+//! every slot along with it, so no slot may ever be left holding a value that
+//! was moved out. Instead of `ptr::read`, the methods that move elements out
+//! of the vector (`pop`, `remove`, `swap_remove`, `drain`, `into_iter_owned`
+//! and `RingVec::pop_front`) use `mem::take`, which leaves `T::default()`
+//! behind. They therefore require `T: Default` rather than `T: Clone`. Types
+//! that are neither can use [`UninitStackVec`], which owns no value it hasn't
+//! been given.
+//!
+//! For the same reason, `push` assigns into its slot, dropping the value that
+//! was there, and `truncate` and `clear` only shorten the vector: the elements
+//! they remove stay in the storage until they're overwritten or the storage
+//! is dropped.
+
 #![no_std]
 
 #[cfg(test)]
 mod tests;
 
-use core::ops::{Deref, DerefMut, Index, IndexMut};
+use core::fmt;
+use core::mem::{ManuallyDrop, MaybeUninit};
+use core::str::{self, Utf8Error};
+use core::ops::{Bound, Deref, DerefMut, Index, IndexMut, RangeBounds};
+
+use shim::io;
+use shim::ioerr;
 
 /// A contiguous array type backed by a slice.
 ///
@@ -13,6 +39,13 @@ use core::ops::{Deref, DerefMut, Index, IndexMut};
 /// result, StackVec's capacity is bounded by the user-supplied slice. This
 /// results in push being fallible: if push is called when the vector is
 /// full, an Err is returned.
+///
+/// Every slot of the backing slice always holds a valid value owned by the
+/// slice, whether or not it's part of the vector: the slots past the length
+/// hold the slice's original contents or elements removed from the vector,
+/// and are dropped when `push` overwrites them or along with the slice. So
+/// that nothing is dropped twice, methods that move an element out of the
+/// vector leave `T::default()` in its slot, and need `T: Default`.
 #[derive(Debug)]
 pub struct StackVec<'a, T: 'a> {
     storage: &'a mut [T],
@@ -26,6 +59,16 @@ impl<'a, T> StackVec<'a, T> {
         StackVec { storage, len: 0 }
     }
 
+    /// Constructs a new, empty StackVec<T> using the array storage as the
+    /// backing store. This is the same as `new`, but makes it plain at the
+    /// call site that the capacity is `N`, a compile-time constant.
+    ///
+    /// StackVec isn't itself generic over its capacity, so there's no
+    /// associated `CAPACITY` constant; `capacity()` returns `N` at run time.
+    pub fn from_array<const N: usize>(storage: &'a mut [T; N]) -> StackVec<'a, T> {
+        StackVec::new(storage)
+    }
+
     /// Constructs a new StackVec<T> using storage as the backing store.
     /// The first len elements of storage are treated as if they were `push`ed
     /// onto self. The returned StackVec will be able to hold a total of
@@ -44,6 +87,27 @@ impl<'a, T> StackVec<'a, T> {
         StackVec { storage, len }
     }
 
+    /// Constructs a new StackVec<T> using storage as the backing store and
+    /// pushes every item yielded by iter onto it.
+    ///
+    /// # Error
+    ///
+    /// If iter yields more than storage.len() items, an Err is returned and
+    /// the items pushed so far are left in storage. Otherwise, Ok is
+    /// returned.
+    pub fn try_from_iter<I: IntoIterator<Item = T>>(
+        storage: &'a mut [T],
+        iter: I,
+    ) -> Result<StackVec<'a, T>, ()> {
+        let mut vec = StackVec::new(storage);
+        for value in iter {
+            if vec.push(value).is_err() {
+                return Err(());
+            }
+        }
+        Ok(vec)
+    }
+
     /// Returns the number of elements this vector can hold.
     pub fn capacity(&self) -> usize {
         self.storage.len()
@@ -52,17 +116,105 @@ impl<'a, T> StackVec<'a, T> {
     /// Shortens the vector, keeping the first len elements. If len is
     /// greater than the vector's current length, this has no effect.
     /// Note that this method has no effect on the capacity of the vector.
+    ///
+    /// The removed elements stay in the backing storage, and are dropped when
+    /// `push` overwrites them or along with the storage.
     pub fn truncate(&mut self, len: usize) {
         if len < self.len {
-            for i in len..self.len {
-                unsafe {
-                    core::ptr::drop_in_place(&mut self.storage[i]);
-                }
-            }
             self.len = len;
         }
     }
 
+    /// Removes every element of the vector, like `truncate(0)`. Note that
+    /// this method has no effect on the capacity of the vector.
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    /// Retains only the elements for which f returns true, removing the rest
+    /// like `truncate`. The retained elements keep their order.
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, mut f: F) {
+        let mut kept = 0;
+        for i in 0..self.len {
+            if f(&self.storage[i]) {
+                self.storage.swap(i, kept);
+                kept += 1;
+            }
+        }
+        self.truncate(kept);
+    }
+
+    /// Removes consecutive elements for which same returns true, like
+    /// `truncate`. same is passed each element and the last element kept
+    /// before it; the first of each run of such elements is kept.
+    pub fn dedup_by<F: FnMut(&mut T, &mut T) -> bool>(&mut self, mut same: F) {
+        if self.len <= 1 {
+            return;
+        }
+
+        let mut kept = 1;
+        for i in 1..self.len {
+            let (done, rest) = self.storage.split_at_mut(i);
+            if !same(&mut rest[0], &mut done[kept - 1]) {
+                self.storage.swap(i, kept);
+                kept += 1;
+            }
+        }
+        self.truncate(kept);
+    }
+
+    /// Removes consecutive elements that map to the same key, like
+    /// `truncate`. The first of each run of such elements is kept.
+    pub fn dedup_by_key<K: PartialEq, F: FnMut(&mut T) -> K>(&mut self, mut key: F) {
+        self.dedup_by(|a, b| key(a) == key(b))
+    }
+
+    /// Resizes the vector in-place so that its length is new_len. If the
+    /// vector grows, each new slot is filled with the result of calling f;
+    /// if it shrinks, the elements are removed like `truncate`.
+    ///
+    /// # Error
+    ///
+    /// If new_len > capacity, an Err is returned and the vector is left
+    /// unchanged. Otherwise, Ok is returned.
+    pub fn resize_with<F: FnMut() -> T>(&mut self, new_len: usize, mut f: F) -> Result<(), ()> {
+        if new_len > self.capacity() {
+            return Err(());
+        }
+
+        self.truncate(new_len);
+        while self.len < new_len {
+            // The capacity check above guarantees this succeeds.
+            let _ = self.push(f());
+        }
+        Ok(())
+    }
+
+    /// Moves the elements at indices [at, len) into a new vector backed by
+    /// dest_storage, leaving self with the first at elements. The values
+    /// dest_storage held in their place are swapped into the slots of self
+    /// past its new length.
+    ///
+    /// # Error
+    ///
+    /// If at > len or dest_storage is too small to hold the moved elements,
+    /// an Err is returned and self is left unchanged. Otherwise, the new
+    /// vector is returned.
+    pub fn split_off_into<'b>(
+        &mut self,
+        at: usize,
+        dest_storage: &'b mut [T],
+    ) -> Result<StackVec<'b, T>, ()> {
+        if at > self.len || self.len - at > dest_storage.len() {
+            return Err(());
+        }
+
+        let moved = self.len - at;
+        self.storage[at..self.len].swap_with_slice(&mut dest_storage[..moved]);
+        self.len = at;
+        Ok(StackVec { storage: dest_storage, len: moved })
+    }
+
     /// Extracts a slice containing the entire vector, consuming self.
     ///
     /// Note that the returned slice's length will be the length of this vector,
@@ -81,6 +233,101 @@ impl<'a, T> StackVec<'a, T> {
         &mut self.storage[..self.len]
     }
 
+    /// Returns a raw pointer to the vector's backing storage.
+    pub fn as_ptr(&self) -> *const T {
+        self.storage.as_ptr()
+    }
+
+    /// Returns a raw mutable pointer to the vector's backing storage. Every
+    /// slot holds a valid value, so writing one with `core::ptr::write`
+    /// leaks its previous occupant unless T is `Copy`.
+    pub fn as_mut_ptr(&mut self) -> *mut T {
+        self.storage.as_mut_ptr()
+    }
+
+    /// Returns the part of the backing storage past the end of the vector,
+    /// from len to capacity.
+    ///
+    /// These slots aren't part of the vector, but hold valid values like the
+    /// rest of the storage, so assigning to them drops their previous
+    /// occupant as usual. Once filled, they can be made part of the vector
+    /// with `set_len`.
+    pub fn spare_capacity_mut(&mut self) -> &mut [T] {
+        &mut self.storage[self.len..]
+    }
+
+    /// Sets the length of the vector to new_len without dropping or
+    /// initializing any element. Growing the vector makes the values in the
+    /// slots up to new_len part of it, e.g. those written through
+    /// `spare_capacity_mut`; shrinking it is the same as `truncate`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if new_len exceeds the capacity.
+    pub fn set_len(&mut self, new_len: usize) {
+        assert!(
+            new_len <= self.capacity(),
+            "New length {} exceeds storage capacity {}",
+            new_len,
+            self.capacity()
+        );
+        self.len = new_len;
+    }
+
+    /// Returns a reference to the element at index, or None if index >= len.
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.as_slice().get(index)
+    }
+
+    /// Returns a mutable reference to the element at index, or None if
+    /// index >= len.
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        self.as_mut_slice().get_mut(index)
+    }
+
+    /// Returns a reference to the first element, or None if the vector is
+    /// empty.
+    pub fn first(&self) -> Option<&T> {
+        self.as_slice().first()
+    }
+
+    /// Returns a reference to the last element, or None if the vector is
+    /// empty.
+    pub fn last(&self) -> Option<&T> {
+        self.as_slice().last()
+    }
+
+    /// Returns a mutable reference to the last element, or None if the vector
+    /// is empty.
+    pub fn last_mut(&mut self) -> Option<&mut T> {
+        self.as_mut_slice().last_mut()
+    }
+
+    /// Reverses the order of the elements in the vector, in place.
+    pub fn reverse(&mut self) {
+        self.as_mut_slice().reverse()
+    }
+
+    /// Rotates the elements of the vector in place such that the first mid
+    /// elements move to the end.
+    ///
+    /// # Panics
+    ///
+    /// Panics if mid > len.
+    pub fn rotate_left(&mut self, mid: usize) {
+        self.as_mut_slice().rotate_left(mid)
+    }
+
+    /// Rotates the elements of the vector in place such that the last k
+    /// elements move to the front.
+    ///
+    /// # Panics
+    ///
+    /// Panics if k > len.
+    pub fn rotate_right(&mut self, k: usize) {
+        self.as_mut_slice().rotate_right(k)
+    }
+
     /// Returns the number of elements in the vector (its 'length').
     pub fn len(&self) -> usize {
         self.len
@@ -100,39 +347,339 @@ impl<'a, T> StackVec<'a, T> {
     ///
     /// # Error
     ///
-    /// If this vector is full, an Err is returned. Otherwise, Ok is returned.
-    pub fn push(&mut self, value: T) -> Result<(), ()> {
+    /// If this vector is full, value is returned in an Err. Otherwise, Ok is
+    /// returned.
+    pub fn push(&mut self, value: T) -> Result<(), T> {
         if self.is_full() {
-            Err(())
+            Err(value)
         } else {
+            // The slot's previous occupant isn't part of the vector, and is
+            // dropped by the assignment.
             self.storage[self.len] = value;
             self.len += 1;
             Ok(())
         }
     }
 
+    /// Moves all elements of other to the back of this vector, leaving other
+    /// empty. The elements are moved, not cloned: they're swapped with the
+    /// values in the spare slots of this vector, which take their place in
+    /// other's storage.
+    ///
+    /// # Error
+    ///
+    /// If other's elements don't fit in the vector's remaining capacity, an
+    /// Err is returned and both vectors are left untouched. Otherwise, Ok is
+    /// returned.
+    pub fn append(&mut self, other: &mut StackVec<'_, T>) -> Result<(), ()> {
+        if other.len > self.capacity() - self.len {
+            return Err(());
+        }
+
+        let (start, end) = (self.len, self.len + other.len);
+        self.storage[start..end].swap_with_slice(other.as_mut_slice());
+        self.len = end;
+        other.len = 0;
+        Ok(())
+    }
+
+    /// Inserts value at position index, shifting all elements after it to
+    /// the right.
+    ///
+    /// # Error
+    ///
+    /// If this vector is full, value is returned in an Err. Otherwise, Ok is
+    /// returned.
+    ///
+    /// # Panics
+    ///
+    /// Panics if index > len.
+    pub fn insert(&mut self, index: usize, value: T) -> Result<(), T> {
+        assert!(
+            index <= self.len,
+            "Insertion index {} exceeds length {}",
+            index,
+            self.len
+        );
+        self.push(value)?;
+        self.storage[index..self.len].rotate_right(1);
+        Ok(())
+    }
+
     /// Returns an iterator over the elements of the vector.
     pub fn iter(&self) -> core::slice::Iter<'_, T> {
         self.as_slice().iter()
     }
 
-    /// Returns a mutable iterator over the elements of the vector.
+    /// Returns a mutable iterator over the elements of the vector. The
+    /// iterator borrows the vector mutably, so its length can't change while
+    /// the iterator is alive.
     pub fn iter_mut(&mut self) -> core::slice::IterMut<'_, T> {
         self.as_mut_slice().iter_mut()
     }
+
+    /// Returns an iterator over chunks of n elements of the vector. The last
+    /// chunk is shorter if len is not a multiple of n.
+    ///
+    /// # Panics
+    ///
+    /// Panics if n is 0.
+    pub fn chunks_active(&self, n: usize) -> core::slice::Chunks<'_, T> {
+        self.as_slice().chunks(n)
+    }
+
+    /// Returns an iterator over mutable chunks of n elements of the vector.
+    /// The last chunk is shorter if len is not a multiple of n. Slots past
+    /// len are never included.
+    ///
+    /// # Panics
+    ///
+    /// Panics if n is 0.
+    pub fn chunks_mut(&mut self, n: usize) -> core::slice::ChunksMut<'_, T> {
+        self.as_mut_slice().chunks_mut(n)
+    }
+
+    /// Splits the vector into mutable arrays of `N` elements, followed by
+    /// the remaining `len % N` elements that don't fill an array.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `N` is 0.
+    pub fn as_chunks_mut<const N: usize>(&mut self) -> (&mut [[T; N]], &mut [T]) {
+        self.as_mut_slice().as_chunks_mut()
+    }
+
+    /// Returns an iterator over all overlapping windows of n elements of the
+    /// vector.
+    ///
+    /// # Panics
+    ///
+    /// Panics if n is 0.
+    pub fn windows_active(&self, n: usize) -> core::slice::Windows<'_, T> {
+        self.as_slice().windows(n)
+    }
+
+    /// Returns the first element of the vector and the rest of its elements,
+    /// or None if it is empty.
+    pub fn split_first(&self) -> Option<(&T, &[T])> {
+        self.as_slice().split_first()
+    }
+
+    /// Returns mutable references to the first element of the vector and the
+    /// rest of its elements, or None if it is empty.
+    pub fn split_first_mut(&mut self) -> Option<(&mut T, &mut [T])> {
+        self.as_mut_slice().split_first_mut()
+    }
+
+    /// Returns the last element of the vector and the rest of its elements,
+    /// or None if it is empty.
+    pub fn split_last(&self) -> Option<(&T, &[T])> {
+        self.as_slice().split_last()
+    }
+
+    /// Returns mutable references to the last element of the vector and the
+    /// rest of its elements, or None if it is empty.
+    pub fn split_last_mut(&mut self) -> Option<(&mut T, &mut [T])> {
+        self.as_mut_slice().split_last_mut()
+    }
 }
 
-impl<'a, T: Clone> StackVec<'a, T> {
+impl<'a, T: Default> StackVec<'a, T> {
     /// If this vector is not empty, removes the last element from this vector
-    /// by cloning it and returns it. Otherwise returns None.
+    /// by moving it out of the backing storage and returns it. Otherwise
+    /// returns None.
     pub fn pop(&mut self) -> Option<T> {
         if self.len == 0 {
             None
         } else {
             self.len -= 1;
-            Some(self.storage[self.len].clone())
+            Some(core::mem::take(&mut self.storage[self.len]))
         }
     }
+
+    /// Removes and returns the element at position index, shifting all
+    /// elements after it to the left.
+    ///
+    /// # Panics
+    ///
+    /// Panics if index >= len.
+    pub fn remove(&mut self, index: usize) -> T {
+        assert!(
+            index < self.len,
+            "Removal index {} is out of bounds for length {}",
+            index,
+            self.len
+        );
+
+        self.storage[index..self.len].rotate_left(1);
+        self.len -= 1;
+        core::mem::take(&mut self.storage[self.len])
+    }
+
+    /// Removes and returns the element at position index, replacing it with
+    /// the last element of the vector. This does not preserve ordering, but
+    /// is O(1).
+    ///
+    /// # Panics
+    ///
+    /// Panics if index >= len.
+    pub fn swap_remove(&mut self, index: usize) -> T {
+        assert!(
+            index < self.len,
+            "Removal index {} is out of bounds for length {}",
+            index,
+            self.len
+        );
+
+        self.storage.swap(index, self.len - 1);
+        self.len -= 1;
+        core::mem::take(&mut self.storage[self.len])
+    }
+
+    /// Returns an iterator that consumes this vector, yielding its elements
+    /// by value. Elements that aren't yielded are left in the backing
+    /// storage.
+    pub fn into_iter_owned(self) -> StackVecIntoIter<'a, T> {
+        StackVecIntoIter { vec: self, index: 0 }
+    }
+
+    /// Removes the elements in range from the vector, returning an iterator
+    /// that yields them by value. When the iterator is dropped, the elements
+    /// after range are shifted down to close the gap, and any elements it
+    /// hasn't yielded are left in the backing storage past the new length.
+    ///
+    /// If the iterator is leaked instead, the vector is left truncated to the
+    /// start of range.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the start of range is greater than its end or if its end is
+    /// greater than len.
+    pub fn drain<R: RangeBounds<usize>>(&mut self, range: R) -> Drain<'_, 'a, T> {
+        let start = match range.start_bound() {
+            Bound::Included(&n) => n,
+            Bound::Excluded(&n) => n + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&n) => n + 1,
+            Bound::Excluded(&n) => n,
+            Bound::Unbounded => self.len,
+        };
+        assert!(
+            start <= end && end <= self.len,
+            "Drain range {}..{} is out of bounds for length {}",
+            start,
+            end,
+            self.len
+        );
+
+        let len = self.len;
+        self.len = start;
+        Drain { vec: self, next: start, end, len }
+    }
+
+    /// Shortens the vector to at elements like `truncate`, but returns an
+    /// iterator yielding the removed elements [at, len) by value. This is
+    /// `drain(at..)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if at > len.
+    pub fn drain_from(&mut self, at: usize) -> Drain<'_, 'a, T> {
+        self.drain(at..)
+    }
+}
+
+impl<'a, T: Clone> StackVec<'a, T> {
+    /// Assigns a clone of value to every element of the vector. Slots past
+    /// the vector's length are left untouched.
+    pub fn fill(&mut self, value: T) {
+        for slot in self.iter_mut() {
+            *slot = value.clone();
+        }
+    }
+
+    /// Clones and appends all elements in other to the back of this vector.
+    ///
+    /// # Error
+    ///
+    /// If other doesn't fit in the vector's remaining capacity, an Err is
+    /// returned and nothing is appended. Otherwise, Ok is returned.
+    pub fn extend_from_slice(&mut self, other: &[T]) -> Result<(), ()> {
+        if other.len() > self.capacity() - self.len {
+            return Err(());
+        }
+
+        for value in other {
+            // The capacity check above guarantees this succeeds.
+            let _ = self.push(value.clone());
+        }
+        Ok(())
+    }
+
+    /// Clones the elements of this vector into storage, returning a new
+    /// vector backed by it.
+    ///
+    /// # Error
+    ///
+    /// If storage can't hold every element of this vector, an Err is
+    /// returned and nothing is cloned. Otherwise, Ok is returned.
+    pub fn clone_into_storage<'b>(&self, storage: &'b mut [T]) -> Result<StackVec<'b, T>, ()> {
+        let mut clone = StackVec::new(storage);
+        clone.extend_from_slice(self.as_slice())?;
+        Ok(clone)
+    }
+}
+
+impl<'a, T: PartialEq> StackVec<'a, T> {
+    /// Returns true if the vector contains an element equal to x. Slots past
+    /// the vector's length are never considered.
+    pub fn contains(&self, x: &T) -> bool {
+        self.as_slice().contains(x)
+    }
+
+    /// Removes consecutive repeated elements like `truncate`, so that only the
+    /// first of each run of equal elements is kept.
+    pub fn dedup(&mut self) {
+        self.dedup_by(|a, b| a == b)
+    }
+}
+
+impl<'a, T: Ord> StackVec<'a, T> {
+    /// Binary searches the sorted vector for x. Slots past the vector's
+    /// length are never considered.
+    ///
+    /// # Error
+    ///
+    /// If x is found, Ok is returned with its index. Otherwise, an Err is
+    /// returned with the index where x could be inserted to keep the vector
+    /// sorted.
+    pub fn binary_search(&self, x: &T) -> Result<usize, usize> {
+        self.as_slice().binary_search(x)
+    }
+}
+
+impl<'a> StackVec<'a, u8> {
+    /// Returns the active region of this vector as a string slice.
+    ///
+    /// # Error
+    ///
+    /// If the active region is not valid UTF-8, the validation error is
+    /// returned.
+    pub fn as_str(&self) -> Result<&str, Utf8Error> {
+        str::from_utf8(self.as_slice())
+    }
+
+    /// Returns the active region of this vector as a mutable string slice.
+    ///
+    /// # Error
+    ///
+    /// If the active region is not valid UTF-8, the validation error is
+    /// returned.
+    pub fn as_mut_str(&mut self) -> Result<&mut str, Utf8Error> {
+        str::from_utf8_mut(self.as_mut_slice())
+    }
 }
 
 /// Allow StackVec to be used as a slice.
@@ -167,49 +714,365 @@ impl<'a, T> IndexMut<usize> for StackVec<'a, T> {
     }
 }
 
+/// An iterator that removes a range of elements from a StackVec, yielding
+/// them by value.
+///
+/// Returned by [`StackVec::drain()`].
+pub struct Drain<'v, 'a, T> {
+    vec: &'v mut StackVec<'a, T>,
+    /// The index of the next element to yield.
+    next: usize,
+    /// The end of the drained range.
+    end: usize,
+    /// The vector's length before draining.
+    len: usize,
+}
+
+impl<'v, 'a, T: Default> Iterator for Drain<'v, 'a, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.next < self.end {
+            let result = core::mem::take(&mut self.vec.storage[self.next]);
+            self.next += 1;
+            Some(result)
+        } else {
+            None
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.end - self.next;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<'v, 'a, T> Drop for Drain<'v, 'a, T> {
+    fn drop(&mut self) {
+        // The drained range, yielded or not, ends up past the tail.
+        let start = self.vec.len;
+        self.vec.storage[start..self.len].rotate_left(self.end - start);
+        self.vec.len = start + self.len - self.end;
+    }
+}
+
+/// Allow pushing the items of an iterator onto a StackVec with `extend`.
+///
+/// Items are pushed until the vector is full. Once it is, extending stops
+/// without pulling any further items from the iterator, so none are lost.
+impl<'a, T> Extend<T> for StackVec<'a, T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let mut iter = iter.into_iter();
+        while !self.is_full() {
+            match iter.next() {
+                Some(value) => {
+                    let _ = self.push(value);
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+/// Writing to a `StackVec<u8>` appends as many bytes as fit.
+impl<'a> io::Write for StackVec<'a, u8> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.is_full() && !buf.is_empty() {
+            return ioerr!(WriteZero, "StackVec is full");
+        }
+
+        let n = core::cmp::min(buf.len(), self.capacity() - self.len);
+        self.storage[self.len..self.len + n].copy_from_slice(&buf[..n]);
+        self.len += n;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Formatting into a `StackVec<u8>` appends each string only if it fits
+/// entirely.
+impl<'a> fmt::Write for StackVec<'a, u8> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.extend_from_slice(s.as_bytes()).map_err(|_| fmt::Error)
+    }
+}
+
+/// Compare StackVecs by their elements, regardless of capacity.
+impl<'a, 'b, T: PartialEq> PartialEq<StackVec<'b, T>> for StackVec<'a, T> {
+    fn eq(&self, other: &StackVec<'b, T>) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+impl<'a, T: Eq> Eq for StackVec<'a, T> {}
+
+/// Allow comparing a StackVec's elements with a slice.
+impl<'a, T: PartialEq> PartialEq<[T]> for StackVec<'a, T> {
+    fn eq(&self, other: &[T]) -> bool {
+        self.as_slice() == other
+    }
+}
+
+/// Allow comparing a StackVec's elements with a slice reference.
+impl<'a, 'b, T: PartialEq> PartialEq<&'b [T]> for StackVec<'a, T> {
+    fn eq(&self, other: &&'b [T]) -> bool {
+        self.as_slice() == *other
+    }
+}
+
 /// An iterator that consumes a StackVec, yielding elements by value.
 ///
-/// This iterator “steals” each element out of the backing storage. Note that
-/// since StackVec does not own its memory (it borrows it from the caller),
-/// this implementation uses unsafe code to move out the elements.
+/// Returned by [`StackVec::into_iter_owned()`].
+///
+/// This iterator takes each element out of the backing storage, leaving
+/// `T::default()` in its place, since StackVec does not own its memory (it
+/// borrows it from the caller).
 pub struct StackVecIntoIter<'a, T> {
     vec: StackVec<'a, T>,
+    /// Index of the next element yielded from the front. Elements are taken
+    /// from the back by shrinking `vec.len`, so the elements not yet yielded
+    /// are always those in `index..vec.len`.
     index: usize,
 }
 
-impl<'a, T> Iterator for StackVecIntoIter<'a, T> {
+impl<'a, T: Default> Iterator for StackVecIntoIter<'a, T> {
     type Item = T;
     fn next(&mut self) -> Option<T> {
         if self.index < self.vec.len {
-            let result = unsafe { core::ptr::read(&self.vec.storage[self.index]) };
+            let result = core::mem::take(&mut self.vec.storage[self.index]);
             self.index += 1;
             Some(result)
         } else {
             None
         }
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.vec.len - self.index;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<'a, T: Default> DoubleEndedIterator for StackVecIntoIter<'a, T> {
+    fn next_back(&mut self) -> Option<T> {
+        if self.index < self.vec.len {
+            self.vec.len -= 1;
+            Some(core::mem::take(&mut self.vec.storage[self.vec.len]))
+        } else {
+            None
+        }
+    }
 }
 
-impl<'a, T> Drop for StackVecIntoIter<'a, T> {
+impl<'a, T: Default> ExactSizeIterator for StackVecIntoIter<'a, T> {}
+
+/// A vector being filled in over uninitialized storage.
+///
+/// Unlike [`StackVec::new()`], which needs storage holding valid values of
+/// `T`, an UninitStackVec borrows `MaybeUninit<T>` storage, so no `Default` or
+/// placeholder values are needed to back it. Elements are pushed into the
+/// uninitialized slots in order, and [`UninitStackVec::into_stack_vec()`] then
+/// turns the initialized prefix of the storage into a StackVec. Elements left
+/// in an UninitStackVec are dropped with it.
+pub struct UninitStackVec<'a, T: 'a> {
+    storage: &'a mut [MaybeUninit<T>],
+    len: usize,
+}
+
+impl<'a, T> UninitStackVec<'a, T> {
+    /// Constructs a new, empty UninitStackVec<T> using storage as the backing
+    /// store. The returned vector will be able to hold storage.len() values.
+    pub fn new(storage: &'a mut [MaybeUninit<T>]) -> UninitStackVec<'a, T> {
+        UninitStackVec { storage, len: 0 }
+    }
+
+    /// Returns the number of elements this vector can hold.
+    pub fn capacity(&self) -> usize {
+        self.storage.len()
+    }
+
+    /// Returns the number of elements initialized so far.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if no element has been initialized yet.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns true if every slot of the storage is initialized.
+    pub fn is_full(&self) -> bool {
+        self.len == self.storage.len()
+    }
+
+    /// Initializes the next slot of the storage with value if the vector is
+    /// not full.
+    ///
+    /// # Error
+    ///
+    /// If this vector is full, value is returned in an Err. Otherwise, Ok is
+    /// returned.
+    pub fn push(&mut self, value: T) -> Result<(), T> {
+        if self.is_full() {
+            return Err(value);
+        }
+
+        self.storage[self.len] = MaybeUninit::new(value);
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Extracts a slice of the initialized elements.
+    pub fn as_slice(&self) -> &[T] {
+        unsafe { core::slice::from_raw_parts(self.storage.as_ptr() as *const T, self.len) }
+    }
+
+    /// Finishes initialization, returning a StackVec backed by the
+    /// initialized prefix of the storage. The returned vector is full: its
+    /// capacity is the number of elements pushed, and the uninitialized rest
+    /// of the storage isn't available to it.
+    ///
+    /// Since `MaybeUninit` storage never drops its contents, every value left
+    /// in it is leaked when it goes out of scope: the elements still in the
+    /// returned vector or removed from it, and the `T::default()` left by
+    /// those moved out. Move the elements out, e.g. with
+    /// [`StackVec::into_iter_owned()`], to drop them.
+    pub fn into_stack_vec(self) -> StackVec<'a, T> {
+        let this = ManuallyDrop::new(self);
+        let len = this.len;
+        let storage = unsafe { core::ptr::read(&this.storage) };
+        let init = unsafe { core::slice::from_raw_parts_mut(storage.as_mut_ptr() as *mut T, len) };
+        StackVec::with_len(init, len)
+    }
+}
+
+impl<'a, T> Drop for UninitStackVec<'a, T> {
     fn drop(&mut self) {
-        for i in self.index..self.vec.len {
-            unsafe {
-                core::ptr::drop_in_place(&mut self.vec.storage[i]);
-            }
+        let init = core::ptr::slice_from_raw_parts_mut(self.storage.as_mut_ptr() as *mut T, self.len);
+        unsafe {
+            core::ptr::drop_in_place(init);
         }
     }
 }
 
-/// Implement IntoIterator for an owned StackVec.
-// impl<'a, T> IntoIterator for StackVec<'a, T> {
-//     type Item = T;
-//     type IntoIter = StackVecIntoIter<'a, T>;
+/// A first-in, first-out queue backed by a slice.
+///
+/// Where StackVec adds and removes elements at its end, a RingVec adds them
+/// at its back with `push_back` and removes them from its front with
+/// `pop_front`. The elements wrap around the end of the backing slice, so the
+/// queue can be filled and drained indefinitely without moving them. As with
+/// StackVec, the capacity is bounded by the backing slice and `push_back`
+/// fails once it's full, and every slot holds a valid value owned by the
+/// slice: `pop_front` leaves `T::default()` behind, and `push_back` drops the
+/// value it overwrites.
+#[derive(Debug)]
+pub struct RingVec<'a, T: 'a> {
+    storage: &'a mut [T],
+    head: usize,
+    len: usize,
+}
 
-//     fn into_iter(self) -> Self::IntoIter {
-//         StackVecIntoIter { vec: self, index: 0 }
-//     }
-// }
+impl<'a, T> RingVec<'a, T> {
+    /// Constructs a new, empty RingVec<T> using storage as the backing
+    /// store. The returned queue will be able to hold storage.len() values.
+    pub fn new(storage: &'a mut [T]) -> RingVec<'a, T> {
+        RingVec { storage, head: 0, len: 0 }
+    }
+
+    /// Returns the number of elements this queue can hold.
+    pub fn capacity(&self) -> usize {
+        self.storage.len()
+    }
+
+    /// Returns the number of elements in the queue.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the queue contains no elements.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns true if the queue is at capacity.
+    pub fn is_full(&self) -> bool {
+        self.len == self.storage.len()
+    }
+
+    /// Appends value to the back of the queue if it is not full.
+    ///
+    /// # Error
+    ///
+    /// If this queue is full, value is returned in an Err. Otherwise, Ok is
+    /// returned.
+    pub fn push_back(&mut self, value: T) -> Result<(), T> {
+        if self.is_full() {
+            return Err(value);
+        }
+
+        let tail = (self.head + self.len) % self.storage.len();
+        self.storage[tail] = value;
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Returns a reference to the element at the front of the queue, or None
+    /// if the queue is empty.
+    pub fn front(&self) -> Option<&T> {
+        self.as_slices().0.first()
+    }
 
+    /// Removes every element of the queue, leaving it empty. The elements
+    /// stay in the backing storage until `push_back` overwrites them or the
+    /// storage is dropped.
+    pub fn clear(&mut self) {
+        self.head = 0;
+        self.len = 0;
+    }
+
+    /// Returns the elements of the queue in order as two slices: the second
+    /// holds the elements that wrapped around to the start of the storage,
+    /// and is empty if none did.
+    pub fn as_slices(&self) -> (&[T], &[T]) {
+        let end = self.head + self.len;
+        if end <= self.storage.len() {
+            (&self.storage[self.head..end], &[])
+        } else {
+            let (wrapped, front) = self.storage.split_at(self.head);
+            (front, &wrapped[..end - self.storage.len()])
+        }
+    }
+
+    /// Returns an iterator over the elements of the queue, from front to
+    /// back.
+    pub fn iter(&self) -> core::iter::Chain<core::slice::Iter<'_, T>, core::slice::Iter<'_, T>> {
+        let (front, wrapped) = self.as_slices();
+        front.iter().chain(wrapped.iter())
+    }
+}
+
+impl<'a, T: Default> RingVec<'a, T> {
+    /// Removes and returns the element at the front of the queue, or None if
+    /// the queue is empty.
+    pub fn pop_front(&mut self) -> Option<T> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let value = core::mem::take(&mut self.storage[self.head]);
+        self.head = (self.head + 1) % self.storage.len();
+        self.len -= 1;
+        Some(value)
+    }
+}
+
+/// Implement IntoIterator for an owned StackVec. This yields references so
+/// that a StackVec can be iterated directly without consuming its elements;
+/// use `into_iter_owned` to move them out instead.
 impl<'a, T> IntoIterator for StackVec<'a, T> {
     type Item = &'a T;
     type IntoIter = core::slice::Iter<'a, T>;
@@ -237,5 +1100,5 @@ impl<'a, T> IntoIterator for &'a mut StackVec<'a, T> {
 
     fn into_iter(self) -> Self::IntoIter {
         self.as_mut_slice().iter_mut()
-    }
+    }
 }
\ No newline at end of file
@@ -1,4 +1,51 @@
-use crate::StackVec;
+extern crate std;
+
+use crate::{RingVec, StackVec, UninitStackVec};
+use core::mem::MaybeUninit;
+use std::string::{String, ToString};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// A value that counts how many times values sharing its counter have been
+/// dropped.
+#[derive(Debug)]
+struct Droppable<'a>(&'a AtomicUsize, usize);
+
+impl Drop for Droppable<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// Counts drops of the `Droppable`s left behind by elements moved out of a
+/// vector, which no test follows.
+static PLACEHOLDER_DROPS: AtomicUsize = AtomicUsize::new(0);
+
+/// The number of a `Droppable` left behind by an element moved out of a
+/// vector.
+const PLACEHOLDER: usize = usize::MAX;
+
+impl Default for Droppable<'_> {
+    fn default() -> Self {
+        Droppable(&PLACEHOLDER_DROPS, PLACEHOLDER)
+    }
+}
+
+/// Returns backing storage of `Droppable`s numbered 0 through 3. Tests let
+/// the storage go out of scope before checking the total number of drops,
+/// so that every value is accounted for exactly once.
+fn droppables(drops: &AtomicUsize) -> [Droppable<'_>; 4] {
+    [
+        Droppable(drops, 0),
+        Droppable(drops, 1),
+        Droppable(drops, 2),
+        Droppable(drops, 3),
+    ]
+}
+
+/// Returns the numbers of the `Droppable`s in `values`.
+fn numbers(values: &[Droppable]) -> std::vec::Vec<usize> {
+    values.iter().map(|d| d.1).collect()
+}
 
 #[test]
 fn assignment_text_example() {
@@ -28,6 +75,21 @@ fn len_and_capacity_ok() {
     assert!(!stack_vec.is_full());
 }
 
+#[test]
+fn from_array() {
+    let mut storage = [0u8; 16];
+    let mut vec = StackVec::from_array(&mut storage);
+    assert_eq!(vec.capacity(), 16);
+    assert!(vec.is_empty());
+
+    for i in 0..16 {
+        vec.push(i).expect("capacity is 16");
+    }
+    assert!(vec.is_full());
+    assert_eq!(vec.push(16), Err(16));
+    assert_eq!(vec.as_slice(), &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]);
+}
+
 #[test]
 #[should_panic]
 fn index_oob() {
@@ -211,7 +273,7 @@ fn errors() {
         assert_eq!(vec.push(i), Ok(()));
     }
     for i in 0..1024 {
-        assert_eq!(vec.push(i), Err(()));
+        assert_eq!(vec.push(i), Err(i));
     }
     for i in 1023..=0 {
         assert_eq!(vec.pop(), Some(i));
@@ -220,3 +282,1146 @@ fn errors() {
         assert_eq!(vec.pop(), None);
     }
 }
+
+#[test]
+fn extend_from_slice() {
+    let mut storage = [0u8; 4];
+    let mut vec = StackVec::new(&mut storage);
+    assert_eq!(vec.extend_from_slice(&[]), Ok(()));
+    assert!(vec.is_empty());
+
+    assert_eq!(vec.extend_from_slice(&[1, 2]), Ok(()));
+    assert_eq!(vec.extend_from_slice(&[3, 4, 5]), Err(()));
+    assert_eq!(vec.as_slice(), &[1, 2]);
+
+    assert_eq!(vec.extend_from_slice(&[3, 4]), Ok(()));
+    assert_eq!(vec.as_slice(), &[1, 2, 3, 4]);
+    assert!(vec.is_full());
+    assert_eq!(vec.extend_from_slice(&[]), Ok(()));
+    assert_eq!(vec.extend_from_slice(&[5]), Err(()));
+    assert_eq!(vec.as_slice(), &[1, 2, 3, 4]);
+}
+
+#[test]
+fn insert_and_remove() {
+    let mut storage = [0usize; 4];
+    let mut vec = StackVec::new(&mut storage);
+    assert_eq!(vec.insert(0, 2), Ok(()));
+    assert_eq!(vec.insert(0, 1), Ok(()));
+    assert_eq!(vec.insert(2, 4), Ok(()));
+    assert_eq!(vec.insert(2, 3), Ok(()));
+    assert_eq!(vec.as_slice(), &[1, 2, 3, 4]);
+    assert_eq!(vec.insert(1, 5), Err(5));
+    assert_eq!(vec.as_slice(), &[1, 2, 3, 4]);
+
+    assert_eq!(vec.remove(1), 2);
+    assert_eq!(vec.as_slice(), &[1, 3, 4]);
+    assert_eq!(vec.remove(2), 4);
+    assert_eq!(vec.remove(0), 1);
+    assert_eq!(vec.as_slice(), &[3]);
+    assert_eq!(vec.insert(1, 6), Ok(()));
+    assert_eq!(vec.as_slice(), &[3, 6]);
+}
+
+#[test]
+fn insert_and_remove_drops() {
+    let drops = AtomicUsize::new(0);
+    {
+        let mut storage = droppables(&drops);
+        let mut vec = StackVec::with_len(&mut storage[..], 3);
+
+        let removed = vec.remove(0);
+        assert_eq!(removed.1, 0);
+        assert_eq!(numbers(&vec), [1, 2]);
+        drop(removed);
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+
+        vec.insert(1, Droppable(&drops, 10)).expect("has space");
+        assert_eq!(numbers(&vec), [1, 10, 2]);
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+        assert_eq!(numbers(&storage), [1, 10, 2, 3]);
+    }
+    assert_eq!(drops.load(Ordering::SeqCst), 5);
+}
+
+#[test]
+#[should_panic]
+fn insert_past_len() {
+    let mut storage = [0usize; 4];
+    let mut vec = StackVec::new(&mut storage);
+    let _ = vec.insert(1, 1);
+}
+
+#[test]
+#[should_panic]
+fn remove_oob() {
+    let mut storage = [0usize; 4];
+    let mut vec = StackVec::with_len(&mut storage, 2);
+    vec.remove(2);
+}
+
+#[test]
+fn swap_remove() {
+    let mut storage = [0usize; 4];
+    let mut vec = StackVec::new(&mut storage);
+    for i in 1..=4 {
+        vec.push(i).expect("cap = 4");
+    }
+
+    assert_eq!(vec.swap_remove(0), 1);
+    assert_eq!(vec.as_slice(), &[4, 2, 3]);
+    assert_eq!(vec.swap_remove(0), 4);
+    assert_eq!(vec.as_slice(), &[3, 2]);
+    assert_eq!(vec.swap_remove(1), 2);
+    assert_eq!(vec.as_slice(), &[3]);
+    assert_eq!(vec.swap_remove(0), 3);
+    assert!(vec.is_empty());
+}
+
+#[test]
+fn swap_remove_drops() {
+    let drops = AtomicUsize::new(0);
+    {
+        let mut storage = droppables(&drops);
+        let mut vec = StackVec::with_len(&mut storage[..], 3);
+
+        let removed = vec.swap_remove(0);
+        assert_eq!(removed.1, 0);
+        assert_eq!(numbers(&vec), [2, 1]);
+        drop(removed);
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+
+        // The vacated slot holds a placeholder, not a copy of the last element.
+        assert_eq!(numbers(&storage), [2, 1, PLACEHOLDER, 3]);
+    }
+    assert_eq!(drops.load(Ordering::SeqCst), 4);
+}
+
+#[test]
+#[should_panic]
+fn swap_remove_oob() {
+    let mut storage = [0usize; 4];
+    let mut vec = StackVec::with_len(&mut storage, 2);
+    vec.swap_remove(2);
+}
+
+#[test]
+fn into_iter_owned() {
+    let mut storage = [0usize; 4];
+    let mut vec = StackVec::new(&mut storage);
+    for i in 0..3 {
+        vec.push(i * 10).expect("cap = 4");
+    }
+    let mut iter = vec.into_iter_owned();
+    assert_eq!(iter.next(), Some(0));
+    assert_eq!(iter.next(), Some(10));
+    assert_eq!(iter.next(), Some(20));
+    assert_eq!(iter.next(), None);
+
+    let drops = AtomicUsize::new(0);
+    {
+        let mut storage = droppables(&drops);
+        let mut iter = StackVec::with_len(&mut storage[..], 4).into_iter_owned();
+        let first = iter.next().expect("has elements");
+        let second = iter.next().expect("has elements");
+        assert_eq!((first.1, second.1), (0, 1));
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+
+        drop(first);
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+        drop(second);
+        assert_eq!(drops.load(Ordering::SeqCst), 2);
+
+        // Elements that weren't yielded stay in the storage.
+        assert_eq!(numbers(&storage), [PLACEHOLDER, PLACEHOLDER, 2, 3]);
+    }
+    assert_eq!(drops.load(Ordering::SeqCst), 4);
+}
+
+#[test]
+fn append() {
+    let (mut storage_a, mut storage_b) = ([0u8; 4], [0u8; 4]);
+    let mut a = StackVec::new(&mut storage_a);
+    let mut b = StackVec::new(&mut storage_b);
+    a.extend_from_slice(&[1, 2]).expect("fits");
+    b.extend_from_slice(&[3, 4]).expect("fits");
+
+    // Exactly fills the remaining capacity.
+    a.append(&mut b).expect("fits exactly");
+    assert_eq!(a.as_slice(), &[1, 2, 3, 4]);
+    assert!(a.is_full());
+    assert!(b.is_empty());
+
+    // Appending an empty vector always succeeds.
+    a.append(&mut b).expect("nothing to move");
+    assert_eq!(a.as_slice(), &[1, 2, 3, 4]);
+
+    // Overflowing leaves both vectors untouched.
+    a.truncate(3);
+    b.extend_from_slice(&[5, 6]).expect("fits");
+    assert_eq!(a.append(&mut b), Err(()));
+    assert_eq!(a.as_slice(), &[1, 2, 3]);
+    assert_eq!(b.as_slice(), &[5, 6]);
+}
+
+#[test]
+fn append_moves_without_duplicates() {
+    let drops = AtomicUsize::new(0);
+    {
+        let (mut storage_a, mut storage_b) = (droppables(&drops), droppables(&drops));
+        let mut a = StackVec::with_len(&mut storage_a[..], 2);
+        let mut b = StackVec::with_len(&mut storage_b[..], 2);
+
+        a.append(&mut b).expect("fits");
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+        assert_eq!(numbers(&a), [0, 1, 0, 1]);
+        assert!(b.is_empty());
+
+        // The values from a's spare slots take the place of the moved elements.
+        assert_eq!(numbers(&storage_b), [2, 3, 2, 3]);
+    }
+    assert_eq!(drops.load(Ordering::SeqCst), 8);
+}
+
+#[test]
+fn drain_from() {
+    let mut storage = [0u8; 8];
+    let mut vec = StackVec::new(&mut storage);
+    vec.extend_from_slice(&[1, 2, 3, 4, 5]).expect("fits");
+
+    let tail: std::vec::Vec<_> = vec.drain_from(2).collect();
+    assert_eq!(tail, [3, 4, 5]);
+    assert_eq!(vec.as_slice(), &[1, 2]);
+
+    assert_eq!(vec.drain_from(2).next(), None);
+    assert_eq!(vec.len(), 2);
+    assert_eq!(vec.drain_from(0).count(), 2);
+    assert!(vec.is_empty());
+}
+
+#[test]
+#[should_panic]
+fn drain_from_oob() {
+    let mut storage = [0u8; 8];
+    let mut vec = StackVec::with_len(&mut storage, 2);
+    vec.drain_from(3);
+}
+
+#[test]
+fn drain_from_drops() {
+    // Fully consumed: the caller owns every removed element.
+    let drops = AtomicUsize::new(0);
+    {
+        let mut storage = droppables(&drops);
+        let mut vec = StackVec::with_len(&mut storage[..], 4);
+        let tail: std::vec::Vec<_> = vec.drain_from(1).collect();
+        assert_eq!(numbers(&tail), [1, 2, 3]);
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+        drop(tail);
+        assert_eq!(drops.load(Ordering::SeqCst), 3);
+    }
+    assert_eq!(drops.load(Ordering::SeqCst), 4);
+
+    // Partially consumed: the rest stay in the storage, past the end.
+    let drops = AtomicUsize::new(0);
+    {
+        let mut storage = droppables(&drops);
+        let mut vec = StackVec::with_len(&mut storage[..], 4);
+        let mut iter = vec.drain_from(1);
+        let first = iter.next().expect("has elements");
+        assert_eq!(first.1, 1);
+        drop(iter);
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+        assert_eq!(vec.len(), 1);
+        drop(first);
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+    }
+    assert_eq!(drops.load(Ordering::SeqCst), 4);
+}
+
+#[test]
+fn truncate_drops() {
+    let drops = AtomicUsize::new(0);
+    {
+        let mut storage = droppables(&drops);
+        let mut vec = StackVec::with_len(&mut storage[..], 4);
+
+        // The removed elements stay in the storage.
+        vec.truncate(2);
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+        assert_eq!(numbers(&vec), [0, 1]);
+        vec.truncate(2);
+        vec.truncate(4);
+        assert_eq!(vec.len(), 2);
+
+        // Pushing over one drops it.
+        vec.push(Droppable(&drops, 10)).expect("has space");
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+
+        let popped = vec.pop().expect("has elements");
+        vec.truncate(0);
+        drop(popped);
+        assert_eq!(drops.load(Ordering::SeqCst), 2);
+        assert_eq!(numbers(&storage), [0, 1, PLACEHOLDER, 3]);
+    }
+    assert_eq!(drops.load(Ordering::SeqCst), 5);
+}
+
+#[test]
+fn into_iter_owned_double_ended() {
+    let mut storage = [0usize; 8];
+    let vec = StackVec::try_from_iter(&mut storage[..], 0..5).unwrap();
+    let mut iter = vec.into_iter_owned();
+    assert_eq!(iter.len(), 5);
+    assert_eq!(iter.next_back(), Some(4));
+    assert_eq!(iter.next(), Some(0));
+    assert_eq!(iter.len(), 3);
+    assert_eq!(iter.next_back(), Some(3));
+    assert_eq!(iter.next_back(), Some(2));
+    assert_eq!(iter.next(), Some(1));
+    assert_eq!(iter.len(), 0);
+    assert_eq!(iter.next(), None);
+    assert_eq!(iter.next_back(), None);
+
+    let mut storage = [0usize; 8];
+    let vec = StackVec::try_from_iter(&mut storage[..], 0..4).unwrap();
+    assert!(vec.into_iter_owned().rev().eq([3, 2, 1, 0].iter().copied()));
+}
+
+#[test]
+fn into_iter_owned_double_ended_drops() {
+    let drops = AtomicUsize::new(0);
+    {
+        let mut storage = droppables(&drops);
+        let mut iter = StackVec::with_len(&mut storage[..], 4).into_iter_owned();
+
+        let back = iter.next_back().expect("has elements");
+        let front = iter.next().expect("has elements");
+        assert_eq!((front.1, back.1), (0, 3));
+
+        drop((front, back));
+        assert_eq!(drops.load(Ordering::SeqCst), 2);
+
+        // The two elements left in the middle stay in the storage.
+        assert_eq!(numbers(&storage), [PLACEHOLDER, 1, 2, PLACEHOLDER]);
+    }
+    assert_eq!(drops.load(Ordering::SeqCst), 4);
+
+    let drops = AtomicUsize::new(0);
+    {
+        let mut storage = droppables(&drops);
+        let mut iter = StackVec::with_len(&mut storage[..], 4).into_iter_owned();
+        while iter.next_back().is_some() && iter.next().is_some() {}
+        assert_eq!(drops.load(Ordering::SeqCst), 4);
+    }
+    assert_eq!(drops.load(Ordering::SeqCst), 4);
+}
+
+#[test]
+fn clear() {
+    let drops = AtomicUsize::new(0);
+    {
+        let mut storage = droppables(&drops);
+        let mut vec = StackVec::with_len(&mut storage[..], 3);
+        vec.clear();
+        assert!(vec.is_empty());
+        assert_eq!(vec.capacity(), 4);
+        vec.clear();
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+    }
+    assert_eq!(drops.load(Ordering::SeqCst), 4);
+}
+
+#[test]
+fn retain() {
+    let mut storage = [0usize; 8];
+    let mut vec = StackVec::new(&mut storage);
+    for i in 0..8 {
+        vec.push(i).expect("cap = 8");
+    }
+    vec.retain(|x| x % 3 != 0);
+    assert_eq!(vec.as_slice(), &[1, 2, 4, 5, 7]);
+
+    let drops = AtomicUsize::new(0);
+    {
+        let mut storage = droppables(&drops);
+        let mut vec = StackVec::with_len(&mut storage[..], 4);
+        vec.retain(|d| d.1 % 2 == 1);
+        assert_eq!(numbers(&vec), [1, 3]);
+
+        vec.retain(|_| true);
+        assert_eq!(vec.len(), 2);
+        vec.retain(|_| false);
+        assert!(vec.is_empty());
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+    }
+    assert_eq!(drops.load(Ordering::SeqCst), 4);
+}
+
+#[test]
+fn pop_without_clone() {
+    let drops = AtomicUsize::new(0);
+    {
+        let mut storage = droppables(&drops);
+        let mut vec = StackVec::with_len(&mut storage[..], 2);
+
+        let last = vec.pop().expect("has elements");
+        assert_eq!(last.1, 1);
+        assert_eq!(vec.len(), 1);
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+        drop(last);
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+
+        vec.truncate(0);
+        assert!(vec.pop().is_none());
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+        assert_eq!(numbers(&storage), [0, PLACEHOLDER, 2, 3]);
+    }
+    assert_eq!(drops.load(Ordering::SeqCst), 4);
+
+    // A popped value isn't freed again along with the storage.
+    let mut storage = [String::new(), String::new()];
+    let mut vec = StackVec::new(&mut storage);
+    vec.push(String::from("hello")).expect("has space");
+    assert_eq!(vec.pop().as_deref(), Some("hello"));
+    assert_eq!(storage, ["", ""]);
+}
+
+#[test]
+fn equality() {
+    let (mut small, mut large) = ([0usize; 3], [0usize; 100]);
+    let mut a = StackVec::new(&mut small);
+    let mut b = StackVec::new(&mut large);
+    assert_eq!(a, b);
+
+    for i in 0..3 {
+        a.push(i).expect("cap = 3");
+        b.push(i).expect("cap = 100");
+    }
+    assert_eq!(a, b);
+    assert_eq!(a, [0, 1, 2][..]);
+    assert_eq!(b, &[0, 1, 2][..]);
+
+    b.push(3).expect("cap = 100");
+    assert_ne!(a, b);
+    assert_ne!(a, &[0, 1][..]);
+    b.truncate(3);
+    assert_eq!(a, b);
+}
+
+#[test]
+fn get() {
+    let mut storage = [7usize; 4];
+    let mut vec = StackVec::new(&mut storage);
+    assert_eq!(vec.get(0), None);
+
+    vec.push(1).expect("cap = 4");
+    vec.push(2).expect("cap = 4");
+    assert_eq!(vec.get(0), Some(&1));
+    assert_eq!(vec.get(1), Some(&2));
+    assert_eq!(vec.get(2), None);
+    assert_eq!(vec.get(4), None);
+    assert_eq!(vec.get(usize::MAX), None);
+
+    *vec.get_mut(1).expect("in range") = 3;
+    assert_eq!(vec.as_slice(), &[1, 3]);
+    assert!(vec.get_mut(2).is_none());
+    assert!(vec.get_mut(10).is_none());
+}
+
+#[test]
+fn first_and_last() {
+    let mut storage = [9usize; 4];
+    let mut vec = StackVec::new(&mut storage);
+    assert_eq!(vec.first(), None);
+    assert_eq!(vec.last(), None);
+    assert!(vec.last_mut().is_none());
+
+    vec.push(1).expect("cap = 4");
+    assert_eq!(vec.first(), Some(&1));
+    assert_eq!(vec.last(), Some(&1));
+
+    vec.push(2).expect("cap = 4");
+    assert_eq!(vec.first(), Some(&1));
+    assert_eq!(vec.last(), Some(&2));
+    *vec.last_mut().expect("non-empty") = 5;
+    assert_eq!(vec.as_slice(), &[1, 5]);
+
+    vec.truncate(0);
+    assert_eq!(vec.first(), None);
+    assert_eq!(vec.last(), None);
+}
+
+#[test]
+fn fill() {
+    let mut storage = [0u8; 4];
+    {
+        let mut vec = StackVec::with_len(&mut storage, 2);
+        vec.fill(7);
+        assert_eq!(vec.as_slice(), &[7, 7]);
+    }
+    assert_eq!(storage, [7, 7, 0, 0]);
+}
+
+#[test]
+fn resize_with() {
+    let mut storage = [0usize; 4];
+    let mut vec = StackVec::new(&mut storage);
+    let mut next = 0;
+    assert_eq!(vec.resize_with(3, || { next += 1; next }), Ok(()));
+    assert_eq!(vec.as_slice(), &[1, 2, 3]);
+
+    assert_eq!(vec.resize_with(1, || 0), Ok(()));
+    assert_eq!(vec.as_slice(), &[1]);
+
+    assert_eq!(vec.resize_with(5, || 9), Err(()));
+    assert_eq!(vec.as_slice(), &[1]);
+    assert_eq!(vec.resize_with(4, || 9), Ok(()));
+    assert_eq!(vec.as_slice(), &[1, 9, 9, 9]);
+
+    let drops = AtomicUsize::new(0);
+    {
+        let mut storage = droppables(&drops);
+        let mut vec = StackVec::with_len(&mut storage[..], 4);
+        assert_eq!(vec.resize_with(1, || unreachable!()), Ok(()));
+        assert_eq!(vec[0].1, 0);
+        assert_eq!(vec.resize_with(2, || Droppable(&drops, 10)), Ok(()));
+        assert_eq!(numbers(&vec), [0, 10]);
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+    }
+    assert_eq!(drops.load(Ordering::SeqCst), 5);
+}
+
+#[test]
+fn push_returns_rejected_value() {
+    let drops = AtomicUsize::new(0);
+    {
+        let mut storage = droppables(&drops);
+        let mut vec = StackVec::with_len(&mut storage[..], 4);
+
+        let rejected = vec.push(Droppable(&drops, 42)).expect_err("full");
+        assert_eq!(rejected.1, 42);
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+        assert_eq!(vec.len(), 4);
+    }
+    assert_eq!(drops.load(Ordering::SeqCst), 5);
+}
+
+#[test]
+fn pop_then_push_drops_once() {
+    let drops = AtomicUsize::new(0);
+    {
+        let mut storage = droppables(&drops);
+        let mut vec = StackVec::with_len(&mut storage[..], 4);
+
+        // Pushing over a popped slot drops only the placeholder left there.
+        for i in 0..3 {
+            let popped = vec.pop().expect("has elements");
+            vec.push(Droppable(&drops, 10 + i)).expect("slot was freed");
+            assert_eq!(drops.load(Ordering::SeqCst), i);
+            drop(popped);
+            assert_eq!(drops.load(Ordering::SeqCst), i + 1);
+        }
+        assert_eq!(vec[3].1, 12);
+
+        // Pushing over slots freed by truncate drops their old occupants.
+        vec.truncate(1);
+        assert_eq!(drops.load(Ordering::SeqCst), 3);
+        vec.push(Droppable(&drops, 20)).expect("slot was freed");
+        vec.push(Droppable(&drops, 21)).expect("slot was freed");
+        assert_eq!(drops.load(Ordering::SeqCst), 5);
+        assert_eq!(numbers(&vec), [0, 20, 21]);
+    }
+    assert_eq!(drops.load(Ordering::SeqCst), 9);
+
+    // Storage passed to new holds live values too, and push drops them.
+    let drops = AtomicUsize::new(0);
+    {
+        let mut storage = droppables(&drops);
+        let mut vec = StackVec::new(&mut storage[..]);
+        vec.push(Droppable(&drops, 10)).expect("has space");
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+    }
+    assert_eq!(drops.load(Ordering::SeqCst), 5);
+}
+
+#[test]
+fn extend() {
+    let mut storage = [0usize; 4];
+    let mut vec = StackVec::new(&mut storage);
+    vec.extend(1..3);
+    assert_eq!(vec.as_slice(), &[1, 2]);
+
+    let mut iter = 3..10;
+    vec.extend(&mut iter);
+    assert_eq!(vec.as_slice(), &[1, 2, 3, 4]);
+    assert_eq!(iter.next(), Some(5));
+}
+
+#[test]
+fn try_from_iter() {
+    let mut storage = [0usize; 4];
+    let vec = StackVec::try_from_iter(&mut storage, 0..4).expect("exact fit");
+    assert_eq!(vec.as_slice(), &[0, 1, 2, 3]);
+    assert!(vec.is_full());
+
+    let mut storage = [0usize; 4];
+    assert!(StackVec::try_from_iter(&mut storage, 0..5).is_err());
+
+    let (placeholders, drops) = (AtomicUsize::new(0), AtomicUsize::new(0));
+    {
+        let mut storage = droppables(&placeholders);
+        let values = (10..20).map(|i| Droppable(&drops, i));
+        assert!(StackVec::try_from_iter(&mut storage[..], values).is_err());
+
+        // The rejected fifth value is dropped; the four pushed before it
+        // replaced the storage's own values.
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+        assert_eq!(placeholders.load(Ordering::SeqCst), 4);
+        assert_eq!(numbers(&storage), [10, 11, 12, 13]);
+    }
+    assert_eq!(drops.load(Ordering::SeqCst), 5);
+}
+
+#[test]
+fn reverse_and_rotate() {
+    let mut storage = [0, 1, 2, 3, 4, 5];
+    {
+        let mut vec = StackVec::with_len(&mut storage, 4);
+        vec.reverse();
+        assert_eq!(vec.as_slice(), &[3, 2, 1, 0]);
+        vec.rotate_left(1);
+        assert_eq!(vec.as_slice(), &[2, 1, 0, 3]);
+        vec.rotate_right(3);
+        assert_eq!(vec.as_slice(), &[1, 0, 3, 2]);
+    }
+    assert_eq!(&storage[4..], &[4, 5]);
+}
+
+#[test]
+#[should_panic]
+fn rotate_past_len() {
+    let mut storage = [0usize; 6];
+    let mut vec = StackVec::with_len(&mut storage, 2);
+    vec.rotate_left(3);
+}
+
+#[test]
+fn drain() {
+    let mut storage = [0usize; 8];
+    let mut vec = StackVec::new(&mut storage);
+    vec.extend(0..6);
+
+    let mut drained = [0usize; 8];
+    let mut out = StackVec::new(&mut drained);
+    out.extend(vec.drain(1..3));
+    assert_eq!(out.as_slice(), &[1, 2]);
+    assert_eq!(vec.as_slice(), &[0, 3, 4, 5]);
+
+    assert_eq!(vec.drain(..2).next(), Some(0));
+    assert_eq!(vec.as_slice(), &[4, 5]);
+
+    assert_eq!(vec.drain(..).count(), 2);
+    assert!(vec.is_empty());
+
+    vec.extend(0..3);
+    assert_eq!(vec.drain(3..).count(), 0);
+    assert_eq!(vec.drain(1..=1).next(), Some(1));
+    assert_eq!(vec.as_slice(), &[0, 2]);
+}
+
+#[test]
+fn drain_drops() {
+    let drops = AtomicUsize::new(0);
+    {
+        let mut storage = droppables(&drops);
+        let mut vec = StackVec::with_len(&mut storage[..], 4);
+
+        let mut drain = vec.drain(1..3);
+        let taken = drain.next().expect("has elements");
+        assert_eq!(taken.1, 1);
+        drop(drain);
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+        assert_eq!(numbers(&vec), [0, 3]);
+        drop(taken);
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+
+        core::mem::forget(vec.drain(..1));
+        assert_eq!(vec.len(), 0);
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+        assert_eq!(numbers(&storage), [0, 3, PLACEHOLDER, 2]);
+    }
+    assert_eq!(drops.load(Ordering::SeqCst), 4);
+}
+
+#[test]
+#[should_panic]
+fn drain_oob() {
+    let mut storage = [0usize; 4];
+    let mut vec = StackVec::with_len(&mut storage, 2);
+    vec.drain(1..3);
+}
+
+#[test]
+fn split_off_into() {
+    let mut storage = [0usize; 4];
+    let mut vec = StackVec::new(&mut storage);
+    vec.extend(1..5);
+
+    let mut dest = [0usize; 4];
+    let tail = vec.split_off_into(4, &mut dest).expect("split at len");
+    assert!(tail.is_empty());
+    assert_eq!(tail.capacity(), 4);
+    assert_eq!(vec.as_slice(), &[1, 2, 3, 4]);
+
+    let mut small = [0usize; 2];
+    assert!(vec.split_off_into(1, &mut small).is_err());
+    assert!(vec.split_off_into(5, &mut small).is_err());
+    assert_eq!(vec.as_slice(), &[1, 2, 3, 4]);
+
+    let tail = vec.split_off_into(2, &mut small).expect("exact fit");
+    assert_eq!(tail.as_slice(), &[3, 4]);
+    assert_eq!(vec.as_slice(), &[1, 2]);
+
+    let mut dest = [0usize; 4];
+    let all = vec.split_off_into(0, &mut dest).expect("split at 0");
+    assert_eq!(all.as_slice(), &[1, 2]);
+    assert!(vec.is_empty());
+}
+
+#[test]
+fn split_off_into_drops() {
+    let drops = AtomicUsize::new(0);
+    {
+        let (mut storage, mut dest) = (droppables(&drops), droppables(&drops));
+        let mut vec = StackVec::with_len(&mut storage[..], 3);
+        let tail = vec.split_off_into(1, &mut dest[..]).expect("fits");
+        assert_eq!(numbers(&tail), [1, 2]);
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+
+        // The values dest held take the place of the moved elements.
+        assert_eq!(numbers(&storage), [0, 0, 1, 3]);
+        assert_eq!(numbers(&dest), [1, 2, 2, 3]);
+    }
+    assert_eq!(drops.load(Ordering::SeqCst), 8);
+}
+
+#[test]
+fn spare_capacity() {
+    let mut storage = [0usize; 4];
+    let mut stack_vec = StackVec::new(&mut storage);
+    stack_vec.push(1).unwrap();
+    assert_eq!(stack_vec.as_ptr(), stack_vec.as_slice().as_ptr());
+
+    let spare = stack_vec.spare_capacity_mut();
+    assert_eq!(spare.len(), 3);
+    spare[0] = 2;
+    spare[1] = 3;
+    stack_vec.set_len(3);
+    assert_eq!(stack_vec.as_slice(), &[1, 2, 3]);
+    assert_eq!(stack_vec.spare_capacity_mut().len(), 1);
+
+    stack_vec.set_len(1);
+    assert_eq!(stack_vec.as_slice(), &[1]);
+}
+
+#[test]
+fn set_len_after_raw_writes() {
+    let mut storage = [0u32; 8];
+    let mut stack_vec = StackVec::new(&mut storage);
+    stack_vec.push(10).unwrap();
+
+    let ptr = stack_vec.as_mut_ptr();
+    for i in 1..5 {
+        unsafe { ptr.add(i).write(10 * (i as u32 + 1)) };
+    }
+
+    stack_vec.set_len(5);
+    assert_eq!(stack_vec.as_slice(), &[10, 20, 30, 40, 50]);
+    assert_eq!(stack_vec.pop(), Some(50));
+    assert_eq!(stack_vec.push(60), Ok(()));
+    assert_eq!(stack_vec.as_slice(), &[10, 20, 30, 40, 60]);
+}
+
+#[test]
+#[should_panic]
+fn set_len_past_capacity() {
+    let mut storage = [0u32; 2];
+    let mut stack_vec = StackVec::new(&mut storage);
+    stack_vec.set_len(3);
+}
+
+#[test]
+fn contains() {
+    let mut storage = [1, 2, 3, 4];
+    let stack_vec = StackVec::with_len(&mut storage, 2);
+    assert!(stack_vec.contains(&1));
+    assert!(stack_vec.contains(&2));
+    assert!(!stack_vec.contains(&3));
+    assert!(!stack_vec.contains(&4));
+}
+
+#[test]
+fn binary_search() {
+    let mut storage = [1, 3, 5, 7, 9, 11];
+    let stack_vec = StackVec::with_len(&mut storage, 4);
+    assert_eq!(stack_vec.binary_search(&1), Ok(0));
+    assert_eq!(stack_vec.binary_search(&7), Ok(3));
+    assert_eq!(stack_vec.binary_search(&4), Err(2));
+    assert_eq!(stack_vec.binary_search(&9), Err(4));
+    assert_eq!(stack_vec.binary_search(&11), Err(4));
+}
+
+#[test]
+fn clone_into_storage() {
+    let mut storage = [1, 2, 3, 4];
+    let stack_vec = StackVec::with_len(&mut storage, 3);
+
+    let mut exact = [0; 3];
+    let clone = stack_vec.clone_into_storage(&mut exact).expect("fits");
+    assert_eq!(clone, stack_vec);
+    assert!(clone.is_full());
+
+    let mut larger = [0; 8];
+    let clone = stack_vec.clone_into_storage(&mut larger).expect("fits");
+    assert_eq!(clone.as_slice(), &[1, 2, 3]);
+    assert_eq!(clone.capacity(), 8);
+
+    let mut small = [0; 2];
+    assert!(stack_vec.clone_into_storage(&mut small).is_err());
+    assert_eq!(small, [0, 0]);
+}
+
+#[test]
+fn dedup() {
+    let mut storage = [1, 1, 2, 3, 3, 3, 1, 1];
+    let mut stack_vec = StackVec::with_len(&mut storage, 6);
+    stack_vec.dedup();
+    assert_eq!(stack_vec.as_slice(), &[1, 2, 3]);
+
+    let mut storage = [1, 2, 3, 4, 5, 7, 8, 10];
+    let mut stack_vec = StackVec::with_len(&mut storage, 8);
+    stack_vec.dedup_by_key(|x| *x / 3);
+    assert_eq!(stack_vec.as_slice(), &[1, 3, 7, 10]);
+
+    let mut storage: [u8; 0] = [];
+    let mut stack_vec = StackVec::new(&mut storage);
+    stack_vec.dedup();
+    assert!(stack_vec.is_empty());
+}
+
+#[test]
+fn dedup_drops() {
+    let drops = AtomicUsize::new(0);
+    {
+        let mut storage = droppables(&drops);
+        let mut vec = StackVec::with_len(&mut storage[..], 4);
+        vec.dedup_by_key(|d| d.1 / 2);
+        assert_eq!(numbers(&vec), [0, 2]);
+
+        vec.dedup_by_key(|d| d.1);
+        assert_eq!(vec.len(), 2);
+        vec.dedup_by(|_, _| true);
+        assert_eq!(numbers(&vec), [0]);
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+    }
+    assert_eq!(drops.load(Ordering::SeqCst), 4);
+}
+
+#[test]
+fn io_write() {
+    use shim::io::Write;
+
+    let mut storage = [0u8; 6];
+    let mut stack_vec = StackVec::new(&mut storage);
+    assert_eq!(stack_vec.write(b"abcd").expect("fits"), 4);
+    assert_eq!(stack_vec.write(b"efgh").expect("partially fits"), 2);
+    assert_eq!(stack_vec.as_slice(), b"abcdef");
+
+    let e = stack_vec.write(b"g").expect_err("full");
+    assert_eq!(e.kind(), shim::io::ErrorKind::WriteZero);
+    assert_eq!(stack_vec.write(b"").expect("nothing to write"), 0);
+    stack_vec.flush().expect("flush is a no-op");
+
+    let mut storage = [0u8; 4];
+    let mut stack_vec = StackVec::new(&mut storage);
+    write!(stack_vec, "{}", 1234).expect("fits");
+    assert_eq!(stack_vec.as_slice(), b"1234");
+    assert!(write!(stack_vec, "5").is_err());
+}
+
+#[test]
+fn fmt_write() {
+    use core::fmt::Write;
+
+    let mut storage = [0u8; 8];
+    let mut stack_vec = StackVec::new(&mut storage);
+    write!(stack_vec, "{}-{}", 12, 345).expect("fits");
+    assert_eq!(stack_vec.as_slice(), b"12-345");
+
+    assert!(write!(stack_vec, "{}", 678).is_err());
+    assert_eq!(stack_vec.as_slice(), b"12-345");
+    write!(stack_vec, "{}", 78).expect("fits");
+    assert!(stack_vec.is_full());
+}
+
+#[test]
+fn as_str() {
+    let mut storage = [0u8; 16];
+    let mut stack_vec = StackVec::new(&mut storage);
+    assert_eq!(stack_vec.as_str(), Ok(""));
+
+    stack_vec.extend_from_slice("héllo".as_bytes()).unwrap();
+    assert_eq!(stack_vec.as_str(), Ok("héllo"));
+    stack_vec.as_mut_str().unwrap().make_ascii_uppercase();
+    assert_eq!(stack_vec.as_str(), Ok("HéLLO"));
+
+    // The vector is still usable after borrowing it as a string.
+    stack_vec.push(b'!').unwrap();
+    assert_eq!(stack_vec.as_str(), Ok("HéLLO!"));
+}
+
+#[test]
+fn as_str_invalid_utf8() {
+    let mut storage = [0u8; 16];
+    let mut stack_vec = StackVec::new(&mut storage);
+    stack_vec.extend_from_slice(&[b'a', 0xc3]).unwrap();
+
+    let e = stack_vec.as_str().expect_err("truncated code point");
+    assert_eq!(e.valid_up_to(), 1);
+    assert!(stack_vec.as_mut_str().is_err());
+
+    // Only the active region is validated.
+    stack_vec.pop();
+    assert_eq!(stack_vec.as_str(), Ok("a"));
+}
+
+#[test]
+fn chunks_and_windows_active() {
+    let mut storage = [0usize; 16];
+    let mut vec = StackVec::new(&mut storage);
+    vec.extend_from_slice(&[1, 2, 3, 4, 5]).unwrap();
+
+    assert_eq!(vec.chunks_active(2).count(), 3);
+    assert_eq!(vec.chunks_active(2).last(), Some(&[5][..]));
+    assert_eq!(vec.chunks_active(16).count(), 1);
+
+    assert_eq!(vec.windows_active(2).count(), 4);
+    assert_eq!(vec.windows_active(5).next(), Some(&[1, 2, 3, 4, 5][..]));
+    assert_eq!(vec.windows_active(6).next(), None);
+
+    vec.clear();
+    assert_eq!(vec.chunks_active(4).count(), 0);
+    assert_eq!(vec.windows_active(1).count(), 0);
+}
+
+#[test]
+fn chunks_mut() {
+    let mut storage = [0u8; 16];
+    {
+        let mut vec = StackVec::new(&mut storage);
+        vec.extend_from_slice(&[1, 2, 3, 4, 5, 6, 7]).unwrap();
+
+        assert_eq!(vec.chunks_mut(2).count(), 4);
+        assert_eq!(vec.chunks_mut(7).count(), 1);
+        for (i, chunk) in vec.chunks_mut(3).enumerate() {
+            chunk.iter_mut().for_each(|b| *b = i as u8);
+        }
+        assert_eq!(vec.as_slice(), &[0, 0, 0, 1, 1, 1, 2]);
+
+        let (chunks, rest) = vec.as_chunks_mut::<2>();
+        assert_eq!(chunks.len(), 7 / 2);
+        assert_eq!(rest, &[2]);
+        chunks.iter_mut().for_each(|chunk| chunk.reverse());
+        rest[0] = 9;
+        assert_eq!(vec.as_slice(), &[0, 0, 1, 0, 1, 1, 9]);
+
+        let (chunks, rest) = vec.as_chunks_mut::<8>();
+        assert!(chunks.is_empty());
+        assert_eq!(rest.len(), 7);
+
+        // Slots past len are left alone.
+        vec.truncate(4);
+        vec.chunks_mut(1).for_each(|chunk| chunk[0] = 0xff);
+        vec.as_chunks_mut::<4>().0[0] = [0xee; 4];
+        assert_eq!(vec.len(), 4);
+    }
+    assert_eq!(&storage[..8], &[0xee, 0xee, 0xee, 0xee, 1, 1, 9, 0]);
+}
+
+/// Returns `N` uninitialized slots.
+fn uninit<T, const N: usize>() -> [MaybeUninit<T>; N] {
+    unsafe { MaybeUninit::uninit().assume_init() }
+}
+
+#[test]
+fn uninit_storage() {
+    let mut storage: [MaybeUninit<String>; 4] = uninit();
+    let mut vec = UninitStackVec::new(&mut storage);
+    assert!(vec.is_empty());
+    assert_eq!(vec.capacity(), 4);
+
+    vec.push("hello".to_string()).expect("cap = 4");
+    vec.push("world".to_string()).expect("cap = 4");
+    assert_eq!(vec.len(), 2);
+    assert_eq!(vec.as_slice(), &["hello", "world"]);
+
+    let mut vec = vec.into_stack_vec();
+    assert_eq!(vec.as_slice(), &["hello", "world"]);
+    assert!(vec.is_full());
+    assert_eq!(vec.pop().as_deref(), Some("world"));
+    vec.push(String::from("again")).expect("slot was freed");
+    assert_eq!(vec.as_slice(), &["hello", "again"]);
+    vec.clear();
+}
+
+#[test]
+fn uninit_storage_full() {
+    let mut storage: [MaybeUninit<String>; 1] = uninit();
+    let mut vec = UninitStackVec::new(&mut storage);
+    vec.push("a".to_string()).expect("cap = 1");
+    assert!(vec.is_full());
+    assert_eq!(vec.push("b".to_string()).expect_err("full"), "b");
+    assert_eq!(vec.into_stack_vec().as_slice(), &["a"]);
+}
+
+#[test]
+fn uninit_storage_drops() {
+    let drops = AtomicUsize::new(0);
+    let mut storage: [MaybeUninit<Droppable>; 4] = uninit();
+    {
+        let mut vec = UninitStackVec::new(&mut storage);
+        vec.push(Droppable(&drops, 0)).expect("cap = 4");
+        vec.push(Droppable(&drops, 1)).expect("cap = 4");
+    }
+    assert_eq!(drops.load(Ordering::SeqCst), 2);
+
+    let mut vec = UninitStackVec::new(&mut storage);
+    vec.push(Droppable(&drops, 2)).expect("cap = 4");
+    let mut vec = vec.into_stack_vec();
+    assert_eq!(drops.load(Ordering::SeqCst), 2);
+    assert_eq!(vec[0].1, 2);
+    drop(vec.pop());
+    assert_eq!(drops.load(Ordering::SeqCst), 3);
+}
+
+#[test]
+fn split_first_and_last() {
+    let mut storage = [5u8; 4];
+    let mut vec = StackVec::new(&mut storage);
+    assert_eq!(vec.split_first(), None);
+    assert_eq!(vec.split_last(), None);
+    assert_eq!(vec.split_first_mut(), None);
+    assert_eq!(vec.split_last_mut(), None);
+
+    vec.push(1).expect("has space");
+    assert_eq!(vec.split_first(), Some((&1, &[][..])));
+    assert_eq!(vec.split_last(), Some((&1, &[][..])));
+
+    vec.push(2).expect("has space");
+    vec.push(3).expect("has space");
+    assert_eq!(vec.split_first(), Some((&1, &[2, 3][..])));
+    assert_eq!(vec.split_last(), Some((&3, &[1, 2][..])));
+
+    let (first, rest) = vec.split_first_mut().expect("not empty");
+    *first = 10;
+    rest[0] = 20;
+    let (last, rest) = vec.split_last_mut().expect("not empty");
+    *last = 30;
+    assert_eq!(rest, &[10, 20]);
+    assert_eq!(vec.as_slice(), &[10, 20, 30]);
+
+    // The storage past the length is never part of the rest.
+    vec.pop();
+    assert_eq!(vec.split_last(), Some((&20, &[10][..])));
+}
+
+#[test]
+fn ring_vec_fifo() {
+    let mut storage = [0u8; 4];
+    let mut ring = RingVec::new(&mut storage);
+    assert_eq!(ring.capacity(), 4);
+    assert!(ring.is_empty());
+    assert_eq!(ring.pop_front(), None);
+    assert_eq!(ring.front(), None);
+
+    for i in 1..=4 {
+        ring.push_back(i).expect("has space");
+    }
+    assert!(ring.is_full());
+    assert_eq!(ring.push_back(5), Err(5));
+    assert_eq!(ring.front(), Some(&1));
+    assert!(ring.iter().copied().eq([1, 2, 3, 4]));
+
+    assert_eq!(ring.pop_front(), Some(1));
+    assert_eq!(ring.pop_front(), Some(2));
+    assert_eq!(ring.len(), 2);
+}
+
+#[test]
+fn ring_vec_wraparound() {
+    let mut storage = [0u32; 3];
+    let mut ring = RingVec::new(&mut storage);
+    let (mut pushed, mut popped) = (0, 0);
+
+    // Fill and drain repeatedly with varying amounts, so the head and tail
+    // wrap around the storage at every offset.
+    for round in 0..10 {
+        while ring.push_back(pushed).is_ok() {
+            pushed += 1;
+        }
+        assert!(ring.is_full());
+        assert!(ring.iter().copied().eq(popped..pushed));
+
+        for _ in 0..(round % 3 + 1) {
+            assert_eq!(ring.pop_front(), Some(popped));
+            popped += 1;
+        }
+        assert_eq!(ring.len() as u32, pushed - popped);
+        assert!(ring.iter().copied().eq(popped..pushed));
+    }
+
+    let (front, wrapped) = ring.as_slices();
+    assert_eq!(front.len() + wrapped.len(), ring.len());
+
+    while let Some(value) = ring.pop_front() {
+        assert_eq!(value, popped);
+        popped += 1;
+    }
+    assert_eq!(popped, pushed);
+    assert!(ring.is_empty());
+    assert_eq!(ring.iter().next(), None);
+}
+
+#[test]
+fn ring_vec_drops() {
+    let drops = AtomicUsize::new(0);
+    let (a, b, c) = (AtomicUsize::new(0), AtomicUsize::new(0), AtomicUsize::new(0));
+    {
+        let mut storage = droppables(&drops);
+        let mut ring = RingVec::new(&mut storage[..]);
+        ring.push_back(Droppable(&a, 0)).expect("has space");
+        ring.push_back(Droppable(&b, 1)).expect("has space");
+        assert_eq!(drops.load(Ordering::SeqCst), 2);
+
+        // A popped element is owned by the caller alone.
+        drop(ring.pop_front());
+        assert_eq!(a.load(Ordering::SeqCst), 1);
+
+        ring.push_back(Droppable(&c, 2)).expect("has space");
+        ring.clear();
+        assert_eq!(drops.load(Ordering::SeqCst), 3);
+        assert_eq!(numbers(&storage), [PLACEHOLDER, 1, 2, 3]);
+    }
+    assert_eq!((a.load(Ordering::SeqCst), b.load(Ordering::SeqCst), c.load(Ordering::SeqCst)), (1, 1, 1));
+    assert_eq!(drops.load(Ordering::SeqCst), 4);
+}
+
+#[test]
+fn ring_vec_zero_capacity() {
+    let mut storage: [u8; 0] = [];
+    let mut ring = RingVec::new(&mut storage);
+    assert!(ring.is_full());
+    assert_eq!(ring.push_back(1), Err(1));
+    assert_eq!(ring.pop_front(), None);
+}
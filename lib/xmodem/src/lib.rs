@@ -1,6 +1,8 @@
 #![cfg_attr(feature = "no_std", no_std)]
 // #![feature(decl_macro)]
 
+use core::sync::atomic::{AtomicBool, Ordering};
+
 use shim::io;
 use shim::ioerr;
 
@@ -8,21 +10,80 @@ use shim::ioerr;
 mod tests;
 mod read_ext;
 mod progress;
+mod packets;
+#[cfg(all(any(test, feature = "testing"), not(feature = "no_std")))]
+pub mod testing;
 
 pub use progress::{Progress, ProgressFn};
+#[cfg(not(feature = "no_std"))]
+pub use progress::ProgressBar;
+pub use packets::Packets;
 
 use read_ext::ReadExt;
 
 const SOH: u8 = 0x01;
+const STX: u8 = 0x02;
 const EOT: u8 = 0x04;
 const ACK: u8 = 0x06;
 const NAK: u8 = 0x15;
 const CAN: u8 = 0x18;
+const CRC: u8 = 0x43;
+
+/// Default number of attempts made to transfer each packet.
+const DEFAULT_MAX_RETRIES: usize = 10;
+
+/// Number of times a CRC receiver sends `C` before falling back to checksums.
+const CRC_HANDSHAKE_ATTEMPTS: usize = 3;
+
+/// Default number of times a receiver sends its initial `NAK` before giving
+/// up on the sender.
+const DEFAULT_HANDSHAKE_ATTEMPTS: usize = 10;
+
+/// The error-detection scheme used for each packet.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Mode {
+    /// The original 8-bit additive checksum.
+    Checksum,
+    /// The CCITT CRC-16 (XMODEM-CRC), requested by the receiver with `C`.
+    Crc,
+}
+
+/// A per-byte read timeout measured in caller-defined ticks.
+///
+/// `now` is polled while waiting for a byte; once `ticks` have elapsed
+/// without one arriving, the read fails with `io::ErrorKind::TimedOut`.
+/// This keeps the timeout usable without `std::time`.
+#[derive(Debug, Copy, Clone)]
+pub struct Timeout {
+    /// The number of ticks to wait for each byte.
+    pub ticks: u64,
+    /// Returns the current tick count.
+    pub now: fn() -> u64,
+}
+
+/// A summary of a completed transmission, returned by
+/// [`Xmodem::transmit_detailed()`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TransmitStats {
+    /// The number of data bytes sent.
+    pub data_bytes: usize,
+    /// The number of zeroes sent to pad the final packet.
+    pub padded_bytes: usize,
+    /// The number of data packets sent.
+    pub packets: usize,
+}
 
 /// Implementation of the XMODEM protocol.
 pub struct Xmodem<R> {
     packet: u8,
+    transferred: usize,
     started: bool,
+    mode: Mode,
+    block_size: usize,
+    max_retries: usize,
+    handshake_attempts: usize,
+    timeout: Option<Timeout>,
+    cancel: Option<&'static AtomicBool>,
     inner: R,
     progress: ProgressFn,
 }
@@ -32,6 +93,9 @@ impl Xmodem<()> {
     /// If the length of the total data yielded by data is not a multiple
     /// of 128 bytes, the data is padded with zeroes.
     ///
+    /// The receiver chooses the checksum mode: an initial `NAK` selects the
+    /// 8-bit checksum and an initial `C` selects CRC-16.
+    ///
     /// Returns the number of bytes written to to, excluding padding zeroes.
     #[inline]
     pub fn transmit<R, W>(data: R, to: W) -> io::Result<usize>
@@ -43,98 +107,193 @@ impl Xmodem<()> {
     }
 
     /// Transmits data with a progress callback.
-    pub fn transmit_with_progress<R, W>(mut data: R, to: W, f: ProgressFn) -> io::Result<usize>
+    pub fn transmit_with_progress<R, W>(data: R, to: W, f: ProgressFn) -> io::Result<usize>
     where
         W: io::Read + io::Write,
         R: io::Read,
     {
-        let mut transmitter = Xmodem::new_with_progress(to, f);
-        // --- NEW: INITIAL HANDSHAKE ---
-        // Wait for the initial NAK from the receiver.
-        let initial = transmitter.read_byte(true)?;
-        if initial != NAK {
-            return ioerr!(InvalidData, "expected initial NAK");
-        }
-        transmitter.started = true;
-        // --------------------------------
+        Xmodem::new_with_progress(to, f).send(data)
+    }
 
-        let mut packet = [0u8; 128];
-        let mut written = 0;
-        'next_packet: loop {
-            let n = data.read_max(&mut packet)?;
-            // Pad remaining bytes with zeroes.
-            packet[n..].iter_mut().for_each(|b| *b = 0);
+    /// Transmits data like `transmit`, but returns how many data bytes,
+    /// padding bytes and packets were sent. This lets the caller record the
+    /// exact payload length when it isn't known up front.
+    #[inline]
+    pub fn transmit_detailed<R, W>(data: R, to: W) -> io::Result<TransmitStats>
+    where
+        W: io::Read + io::Write,
+        R: io::Read,
+    {
+        Xmodem::new(to).send_detailed(data)
+    }
 
-            if n == 0 {
-                // --- EOT HANDSHAKE (sender) ---
-                // To end transmission, the sender sends:
-                //   EOT, waits for NAK, then sends EOT and waits for ACK.
-                transmitter.write_byte(EOT)?;
-                transmitter.expect_byte(NAK, "NAK after EOT")?;
-                transmitter.write_byte(EOT)?;
-                transmitter.expect_byte(ACK, "ACK after second EOT")?;
-                return Ok(written);
-            }
+    /// Transmits data like `transmit`, but without waiting for the
+    /// receiver's initial `NAK`: packets are sent immediately, using the
+    /// 8-bit checksum.
+    ///
+    /// This is not standard XMODEM. It is only meant for controlled
+    /// environments, such as an in-process pipe or a replayed capture, where
+    /// the receiver is known to be ready and its handshake has already been
+    /// consumed. Against a regular receiver, the unconsumed handshake is
+    /// mistaken for a response to the first packet.
+    #[inline]
+    pub fn transmit_without_handshake<R, W>(data: R, to: W) -> io::Result<usize>
+    where
+        W: io::Read + io::Write,
+        R: io::Read,
+    {
+        Xmodem::new(to).without_handshake().send(data)
+    }
 
-            // Try sending the packet up to 10 times.
-            for _ in 0..10 {
-                match transmitter.write_packet(&packet) {
-                    Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
-                    Err(e) => return Err(e),
-                    Ok(_) => {
-                        written += n;
-                        continue 'next_packet;
-                    }
-                }
-            }
+    /// Receives data using the XMODEM protocol, with the 8-bit checksum.
+    ///
+    /// Unlike `receive_with_progress`, this never sends `C`, so it works
+    /// with any sender even when `from` has no read timeout.
+    #[inline]
+    pub fn receive<R, W>(from: R, into: W) -> io::Result<usize>
+    where
+        R: io::Read + io::Write,
+        W: io::Write,
+    {
+        Xmodem::new(from).recv(into)
+    }
 
-            return ioerr!(BrokenPipe, "bad transmit");
-        }
+    /// Receives data with a progress callback, negotiating the checksum
+    /// mode: `C` is sent up to three times to ask for CRC-16, and the
+    /// receiver falls back to the 8-bit checksum and `NAK` if every read of
+    /// the sender's reply times out. Without a read timeout on `from`, a
+    /// sender that ignores `C` stalls the transfer.
+    ///
+    /// To learn which mode was chosen, receive with
+    /// `Xmodem::new_with_progress(from, f).with_mode(Mode::Crc)` instead and
+    /// call `mode()` once `recv` returns.
+    pub fn receive_with_progress<R, W>(from: R, into: W, f: ProgressFn) -> io::Result<usize>
+    where
+        R: io::Read + io::Write,
+        W: io::Write,
+    {
+        Xmodem::new_with_progress(from, f).with_mode(Mode::Crc).recv(into)
     }
 
-    /// Receives data using the XMODEM protocol.
+    /// Receives data with a progress callback and the 8-bit checksum, like
+    /// `receive`, and also writes a copy of the received data to `log` as
+    /// each packet arrives. `log` receives exactly the bytes
+    /// written to `into`, padding included, so a transfer can be captured for
+    /// inspection without being repeated.
+    pub fn receive_with_tee<R, W, L>(from: R, into: W, log: L, f: ProgressFn) -> io::Result<usize>
+    where
+        R: io::Read + io::Write,
+        W: io::Write,
+        L: io::Write,
+    {
+        Xmodem::new_with_progress(from, f).recv(Tee(into, log))
+    }
+
+    /// Receives data using the XMODEM protocol, writing at most `expected_len`
+    /// bytes to `into` so the padding of the final packet is dropped.
+    ///
+    /// Returns the number of data bytes written to `into`.
     #[inline]
-    pub fn receive<R, W>(from: R, into: W) -> io::Result<usize>
+    pub fn receive_with_len<R, W>(from: R, into: W, expected_len: Option<usize>) -> io::Result<usize>
     where
         R: io::Read + io::Write,
         W: io::Write,
     {
-        Xmodem::receive_with_progress(from, into, progress::noop)
+        Xmodem::new(from).recv_with_len(into, expected_len)
     }
 
-    /// Receives data with a progress callback.
-    pub fn receive_with_progress<R, W>(from: R, mut into: W, f: ProgressFn) -> io::Result<usize>
+    /// Receives data using XMODEM-CRC, falling back to the 8-bit checksum
+    /// if the sender doesn't answer any of the `C` handshakes.
+    #[inline]
+    pub fn receive_crc<R, W>(from: R, into: W) -> io::Result<usize>
     where
         R: io::Read + io::Write,
         W: io::Write,
     {
-        let mut receiver = Xmodem::new_with_progress(from, f);
-        // Receiver immediately sends a NAK to signal readiness.
-        receiver.write_byte(NAK)?;
-        let mut packet = [0u8; 128];
-        let mut received = 0;
-        'next_packet: loop {
-            for _ in 0..10 {
-                match receiver.read_packet(&mut packet) {
-                    Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
-                    Err(e) => return Err(e),
-                    Ok(0) => break 'next_packet, // End-of-transmission.
-                    Ok(n) => {
-                        received += n;
-                        into.write_all(&packet)?;
-                        continue 'next_packet;
-                    }
-                }
-            }
-            return ioerr!(BrokenPipe, "bad receive");
+        Xmodem::new(from).with_mode(Mode::Crc).recv(into)
+    }
+}
+
+/// Returns the `BrokenPipe` error reported once every attempt to transfer
+/// `packet` has failed.
+fn retries_exhausted(msg: &'static str, packet: u8) -> io::Error {
+    #[cfg(not(feature = "no_std"))]
+    return io::Error::new(io::ErrorKind::BrokenPipe, format!("{} (packet {})", msg, packet));
+
+    #[cfg(feature = "no_std")]
+    {
+        let _ = packet;
+        io::Error::new(io::ErrorKind::BrokenPipe, msg)
+    }
+}
+
+/// A writer that writes everything to both `.0` and `.1`.
+struct Tee<A, B>(A, B);
+
+impl<A: io::Write, B: io::Write> io::Write for Tee<A, B> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write_all(buf)?;
+        self.1.write_all(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()?;
+        self.1.flush()
+    }
+}
+
+/// Returns the `InvalidData` error reported when `received` arrived instead
+/// of the byte described by `expected`.
+fn unexpected_byte(expected: &'static str, received: u8) -> io::Error {
+    #[cfg(not(feature = "no_std"))]
+    return io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("expected {}, received {:#04x}", expected, received),
+    );
+
+    #[cfg(feature = "no_std")]
+    {
+        let _ = received;
+        io::Error::new(io::ErrorKind::InvalidData, expected)
+    }
+}
+
+/// Computes the checksum used in `Mode::Checksum`: the sum of all bytes
+/// modulo 256.
+pub const fn get_checksum(buf: &[u8]) -> u8 {
+    let (mut sum, mut i) = (0u8, 0);
+    while i < buf.len() {
+        sum = sum.wrapping_add(buf[i]);
+        i += 1;
+    }
+    sum
+}
+
+/// Computes the CRC-16 used in `Mode::Crc` (CCITT polynomial 0x1021, initial
+/// value 0) of `buf`. The CRC of `b"123456789"` is `0x31C3`.
+pub const fn get_crc16(buf: &[u8]) -> u16 {
+    let (mut crc, mut i) = (0u16, 0);
+    while i < buf.len() {
+        crc ^= (buf[i] as u16) << 8;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x1021 } else { crc << 1 };
+            bit += 1;
         }
-        Ok(received)
+        i += 1;
     }
+    crc
+}
+
+/// Returns `true` if `expected` is the checksum of `buf`.
+pub const fn verify_checksum(buf: &[u8], expected: u8) -> bool {
+    get_checksum(buf) == expected
 }
 
-/// Computes the checksum as the sum of all bytes modulo 256.
-fn get_checksum(buf: &[u8]) -> u8 {
-    buf.iter().fold(0, |a, b| a.wrapping_add(*b))
+/// Returns `true` if `expected` is the CRC-16 of `buf`.
+pub const fn verify_crc16(buf: &[u8], expected: u16) -> bool {
+    get_crc16(buf) == expected
 }
 
 impl<T: io::Read + io::Write> Xmodem<T> {
@@ -142,7 +301,14 @@ impl<T: io::Read + io::Write> Xmodem<T> {
     pub fn new(inner: T) -> Self {
         Xmodem {
             packet: 1,
+            transferred: 0,
             started: false,
+            mode: Mode::Checksum,
+            block_size: 128,
+            max_retries: DEFAULT_MAX_RETRIES,
+            handshake_attempts: DEFAULT_HANDSHAKE_ATTEMPTS,
+            timeout: None,
+            cancel: None,
             inner,
             progress: progress::noop,
         }
@@ -152,17 +318,371 @@ impl<T: io::Read + io::Write> Xmodem<T> {
     pub fn new_with_progress(inner: T, f: ProgressFn) -> Self {
         Xmodem {
             packet: 1,
+            transferred: 0,
             started: false,
+            mode: Mode::Checksum,
+            block_size: 128,
+            max_retries: DEFAULT_MAX_RETRIES,
+            handshake_attempts: DEFAULT_HANDSHAKE_ATTEMPTS,
+            timeout: None,
+            cancel: None,
             inner,
             progress: f,
         }
     }
 
+    /// Returns the number of the next packet to be transferred. After a
+    /// failed transfer, this is the packet that couldn't be transferred.
+    pub fn packet_number(&self) -> u8 {
+        self.packet
+    }
+
+    /// Sets the number of the next packet to be transferred, so that
+    /// `read_packet` expects, and `write_packet` sends, packet `n` next.
+    ///
+    /// This is a non-standard way of resuming an interrupted transfer: the
+    /// protocol has no means of negotiating the starting number, so both
+    /// peers must agree on it out-of-band. A packet numbered `n - 1` is then
+    /// treated as a retransmitted duplicate, as usual.
+    pub fn set_packet_number(&mut self, n: u8) {
+        self.packet = n;
+    }
+
+    /// Returns the number of data bytes transferred so far.
+    pub fn bytes_transferred(&self) -> usize {
+        self.transferred
+    }
+
+    /// Resets the transfer state so that this instance can start a new
+    /// transfer over the same stream: the packet number returns to 1, the
+    /// byte count to zero, and the next `send` or `recv` performs the
+    /// initial handshake again. Nothing is read from or written to the inner
+    /// stream, so this doesn't resynchronize a transfer that was abandoned
+    /// midway; the peer must also be starting afresh.
+    pub fn reset(&mut self) {
+        self.packet = 1;
+        self.transferred = 0;
+        self.started = false;
+    }
+
+    /// Returns a reference to the inner stream.
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the inner stream. Reading from or
+    /// writing to it in the middle of a transfer will corrupt the transfer.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Consumes this instance, returning the inner stream. No bytes are read
+    /// from or written to the stream.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// Sets the checksum mode requested by this instance when receiving.
+    ///
+    /// A transmitter always uses the mode chosen by the receiver's initial
+    /// handshake, so this setting only affects `recv`, or `send` when the
+    /// handshake is skipped with `without_handshake`.
+    pub fn with_mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Returns the checksum mode currently in use. After a `recv` in
+    /// `Mode::Crc`, this is the mode negotiated with the sender.
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Sets the block size used by `send`: either 128 (the default) or 1024
+    /// for XMODEM-1K. A short final block is always sent as 128-byte
+    /// packets. Receivers accept both block sizes regardless of this setting.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is neither 128 nor 1024.
+    pub fn with_block_size(mut self, size: usize) -> Self {
+        assert!(size == 128 || size == 1024, "invalid block size {}", size);
+        self.block_size = size;
+        self
+    }
+
+    /// Returns the block size used by `send`.
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// Skips the receiver's initial handshake: `send` starts sending packets
+    /// right away, in the mode set with `with_mode`, as if the handshake had
+    /// already taken place.
+    ///
+    /// This is not standard XMODEM; see
+    /// [`Xmodem::transmit_without_handshake()`].
+    pub fn without_handshake(mut self) -> Self {
+        self.started = true;
+        self
+    }
+
+    /// Sets the number of attempts `send` and `recv` make for each packet
+    /// before failing with `BrokenPipe`. The default is 10.
+    pub fn with_max_retries(mut self, n: usize) -> Self {
+        self.max_retries = n;
+        self
+    }
+
+    /// Sets the number of times `recv` sends its initial `NAK` while waiting
+    /// for the sender to start, giving up with `TimedOut` once every attempt
+    /// has timed out. The default is 10. Attempts only time out with a
+    /// timeout set by `with_timeout`; otherwise `recv` waits indefinitely.
+    pub fn with_handshake_attempts(mut self, n: usize) -> Self {
+        self.handshake_attempts = n;
+        self
+    }
+
+    /// Sets the timeout applied to every byte read from the inner stream.
+    ///
+    /// With a timeout, the inner stream is polled: reads returning `Ok(0)`
+    /// or `WouldBlock` are retried until a byte arrives or the timeout
+    /// expires. A timed-out packet is retried like one that failed its
+    /// checksum. Passing `None` (the default) keeps reads blocking.
+    pub fn with_timeout(mut self, timeout: Option<Timeout>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets a flag that cancels the transfer when set.
+    ///
+    /// The flag is checked before every packet attempt made by `send` and
+    /// `recv`; once it is set, the transfer is aborted with `cancel` and
+    /// fails with `ConnectionAborted`. Since the flag is shared, it can be
+    /// set from a progress callback or anywhere else.
+    pub fn with_cancel_flag(mut self, flag: &'static AtomicBool) -> Self {
+        self.cancel = Some(flag);
+        self
+    }
+
+    /// Aborts the transfer by sending two consecutive CANs to the peer.
+    pub fn cancel(&mut self) -> io::Result<()> {
+        self.write_byte(CAN)?;
+        self.write_byte(CAN)?;
+        self.flush()
+    }
+
+    /// Cancels the transfer if the cancel flag has been set.
+    fn check_cancelled(&mut self) -> io::Result<()> {
+        match self.cancel {
+            Some(flag) if flag.load(Ordering::Relaxed) => {
+                self.cancel()?;
+                ioerr!(ConnectionAborted, "transfer cancelled")
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Transmits everything yielded by `data` to the inner stream, padding
+    /// the final packet with zeroes.
+    ///
+    /// Returns the number of bytes transmitted, excluding padding zeroes.
+    pub fn send<R: io::Read>(&mut self, data: R) -> io::Result<usize> {
+        self.send_detailed(data).map(|_| self.transferred)
+    }
+
+    /// Transmits everything yielded by `data` like `send`, but returns how
+    /// many data bytes, padding bytes and packets this call sent.
+    pub fn send_detailed<R: io::Read>(&mut self, mut data: R) -> io::Result<TransmitStats> {
+        let mut stats = TransmitStats { data_bytes: 0, padded_bytes: 0, packets: 0 };
+        if !self.started {
+            self.wait_for_receiver()?;
+        }
+        (self.progress)(Progress::Started);
+
+        let mut block = [0u8; 1024];
+        loop {
+            let block_size = self.block_size;
+            let n = data.read_exact_or_eof(&mut block[..block_size])?;
+            if n == 0 {
+                self.write_packet(&[])?;
+                (self.progress)(Progress::Completed { packets: stats.packets, bytes: stats.data_bytes });
+                return Ok(stats);
+            }
+
+            // Short blocks are sent as 128-byte packets.
+            let packet_size = if n == 1024 { 1024 } else { 128 };
+            for chunk in block[..n].chunks(packet_size) {
+                // Pad remaining bytes with zeroes.
+                let mut packet = [0u8; 1024];
+                packet[..chunk.len()].copy_from_slice(chunk);
+                self.send_packet(&packet[..packet_size])?;
+
+                // `send_packet` only returns once the packet is acknowledged,
+                // however many times it was retransmitted, so each chunk is
+                // counted exactly once.
+                stats.data_bytes += chunk.len();
+                stats.padded_bytes += packet_size - chunk.len();
+                stats.packets += 1;
+
+                self.transferred += chunk.len();
+                (self.progress)(Progress::Bytes { transferred: self.transferred });
+            }
+        }
+    }
+
+    /// Sends a single data packet, retrying it up to `max_retries` times.
+    fn send_packet(&mut self, packet: &[u8]) -> io::Result<()> {
+        for _ in 0..self.max_retries {
+            self.check_cancelled()?;
+            match self.write_packet(packet) {
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(ref e) if e.kind() == io::ErrorKind::TimedOut => continue,
+                Err(e) => return Err(e),
+                Ok(_) => return Ok(()),
+            }
+        }
+
+        Err(retries_exhausted("bad transmit", self.packet))
+    }
+
+    /// Receives a transfer from the inner stream, writing every packet to
+    /// `into`.
+    ///
+    /// In `Mode::Crc` the receiver sends `C` up to three times and falls
+    /// back to `Mode::Checksum` if every read of the sender's reply times
+    /// out. Returns the number of bytes received.
+    pub fn recv<W: io::Write>(&mut self, into: W) -> io::Result<usize> {
+        self.recv_with_len(into, None)
+    }
+
+    /// Like `recv`, but when `expected_len` is known only the first
+    /// `expected_len` bytes of the transfer are written to `into`, leaving
+    /// out the zero padding of the final packet.
+    ///
+    /// Returns the number of data bytes written to `into`.
+    pub fn recv_with_len<W: io::Write>(
+        &mut self,
+        mut into: W,
+        expected_len: Option<usize>,
+    ) -> io::Result<usize> {
+        let mut header = Some(self.start_receive()?);
+        let (start, mut packets) = (self.transferred, 0);
+        let mut packet = [0u8; 1024];
+        loop {
+            let n = match self.receive_packet(header.take(), &mut packet)? {
+                0 => break, // End-of-transmission.
+                n => n,
+            };
+
+            let n = match expected_len {
+                Some(len) => n.min(len.saturating_sub(self.transferred)),
+                None => n,
+            };
+            into.write_all(&packet[..n])?;
+            self.transferred += n;
+            packets += 1;
+            (self.progress)(Progress::Bytes { transferred: self.transferred });
+        }
+
+        let bytes = self.transferred - start;
+        (self.progress)(Progress::Completed { packets, bytes });
+        Ok(self.transferred)
+    }
+
+    /// Returns an iterator over the 128-byte blocks of a transfer received
+    /// from the inner stream. See [`Packets`].
+    pub fn packets(self) -> Packets<T> {
+        Packets::new(self)
+    }
+
+    /// Receives the next packet into `packet`, retrying it up to
+    /// `max_retries` times. `header` is the packet's first byte if it has
+    /// already been read. Returns 0 at the end of the transmission.
+    ///
+    /// A retransmitted duplicate of the previous packet has already been
+    /// acknowledged again, so it doesn't count as a failed attempt.
+    fn receive_packet(&mut self, mut header: Option<u8>, packet: &mut [u8]) -> io::Result<usize> {
+        let mut attempts = 0;
+        while attempts < self.max_retries {
+            self.check_cancelled()?;
+            let result = match header.take() {
+                Some(first) => self.read_packet_after(first, packet),
+                None => self.read_packet(packet),
+            };
+
+            match result {
+                Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => attempts += 1,
+                Err(ref e) if e.kind() == io::ErrorKind::TimedOut => {
+                    // Ask the sender to retransmit whatever was lost.
+                    self.write_byte(NAK)?;
+                    attempts += 1;
+                }
+                result => return result,
+            }
+        }
+
+        Err(retries_exhausted("bad receive", self.packet))
+    }
+
+    /// Waits for the receiver's initial `NAK` or `C` and adopts the mode it
+    /// selects.
+    fn wait_for_receiver(&mut self) -> io::Result<()> {
+        (self.progress)(Progress::WaitingHandshake);
+        self.mode = match self.read_byte(true)? {
+            NAK => Mode::Checksum,
+            CRC => Mode::Crc,
+            _ => return ioerr!(InvalidData, "expected initial NAK or C"),
+        };
+        self.started = true;
+        Ok(())
+    }
+
+    /// Signals readiness to the sender and returns the first byte of its
+    /// reply.
+    fn start_receive(&mut self) -> io::Result<u8> {
+        if self.mode == Mode::Crc {
+            for attempt in 0..CRC_HANDSHAKE_ATTEMPTS {
+                self.write_byte(CRC)?;
+                if attempt == 0 {
+                    (self.progress)(Progress::Started);
+                }
+
+                match self.read_byte(true) {
+                    Ok(byte) => return Ok(byte),
+                    Err(ref e) if e.kind() == io::ErrorKind::TimedOut => continue,
+                    Err(e) => return Err(e),
+                }
+            }
+            self.mode = Mode::Checksum;
+        } else {
+            (self.progress)(Progress::Started);
+        }
+
+        // Receiver sends a NAK to signal readiness, and again each time the
+        // sender fails to answer it.
+        for attempt in 0..self.handshake_attempts {
+            if attempt > 0 {
+                (self.progress)(Progress::WaitingHandshake);
+            }
+
+            self.write_byte(NAK)?;
+            match self.read_byte(true) {
+                Ok(byte) => return Ok(byte),
+                Err(ref e) if e.kind() == io::ErrorKind::TimedOut => continue,
+                Err(e) => return Err(e),
+            }
+        }
+
+        ioerr!(TimedOut, "sender never started the transfer")
+    }
+
     /// Reads a single byte from the inner I/O stream.
     /// If abort_on_can is true and the byte is CAN, returns a ConnectionAborted error.
     fn read_byte(&mut self, abort_on_can: bool) -> io::Result<u8> {
         let mut buf = [0u8; 1];
-        self.inner.read_exact(&mut buf)?;
+        self.read_exact(&mut buf)?;
         let byte = buf[0];
         if abort_on_can && byte == CAN {
             return ioerr!(ConnectionAborted, "received CAN");
@@ -170,47 +690,85 @@ impl<T: io::Read + io::Write> Xmodem<T> {
         Ok(byte)
     }
 
-    /// Writes a single byte to the inner I/O stream.
-    fn write_byte(&mut self, byte: u8) -> io::Result<()> {
-        self.inner.write_all(&[byte])
-    }
+    /// Fills `buf` from the inner I/O stream, applying the read timeout (if
+    /// any) to each byte.
+    fn read_exact(&mut self, buf: &mut [u8]) -> io::Result<()> {
+        let timeout = match self.timeout {
+            Some(timeout) => timeout,
+            None => return self.inner.read_exact(buf),
+        };
 
-    /// Reads a byte and compares it to byte. On mismatch, sends a CAN and returns an error.
-    fn expect_byte_or_cancel(&mut self, byte: u8, expected: &'static str) -> io::Result<u8> {
-        let b = self.read_byte(false)?;
-        if b != byte {
-            self.write_byte(CAN)?;
-            if b == CAN {
-                return ioerr!(ConnectionAborted, "received CAN");
-            } else {
-                return ioerr!(InvalidData, expected);
+        let mut filled = 0;
+        let mut start = (timeout.now)();
+        while filled < buf.len() {
+            match self.inner.read(&mut buf[filled..]) {
+                Ok(0) => {}
+                Ok(n) => {
+                    filled += n;
+                    start = (timeout.now)();
+                    continue;
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                Err(e) => return Err(e),
+            }
+
+            if (timeout.now)().wrapping_sub(start) >= timeout.ticks {
+                return ioerr!(TimedOut, "timed out waiting for byte");
             }
         }
-        Ok(b)
+
+        Ok(())
+    }
+
+    /// Writes a single byte to the inner I/O stream.
+    fn write_byte(&mut self, byte: u8) -> io::Result<()> {
+        self.inner.write_all(&[byte])
     }
 
-    /// Reads a byte and verifies that it matches byte.
-    /// For CAN, does not abort on receiving CAN.
+    /// Reads a byte and verifies that it matches byte, cancelling the
+    /// transfer on a mismatch.
+    ///
+    /// A received CAN, unless CAN is the byte expected, means the peer has
+    /// already given up, so it is reported as `ConnectionAborted` without
+    /// sending a CAN back. Any other wrong byte is answered with a CAN and
+    /// reported as `InvalidData` naming the byte received. Errors from the
+    /// read itself, such as `TimedOut`, are returned as is.
     fn expect_byte(&mut self, byte: u8, expected: &'static str) -> io::Result<u8> {
-        let b = if byte == CAN { self.read_byte(false)? } else { self.read_byte(true)? };
-        if b != byte {
-            self.write_byte(CAN)?;
-            return ioerr!(InvalidData, expected);
+        match self.read_byte(false)? {
+            b if b == byte => Ok(b),
+            CAN => ioerr!(ConnectionAborted, "received CAN"),
+            b => {
+                self.write_byte(CAN)?;
+                Err(unexpected_byte(expected, b))
+            }
         }
-        Ok(b)
     }
 
-    /// Reads (downloads) a single packet (128 bytes) from the inner stream.
-    /// If the provided buffer is too small, returns UnexpectedEof.
+    /// Reads (downloads) a single packet (128 bytes, or 1024 bytes for an
+    /// `STX` packet) from the inner stream and returns the number of data
+    /// bytes read into `buf`.
+    /// `buf` must be at least 128 bytes long, and at least 1024 bytes long to
+    /// receive an `STX` packet; otherwise, returns `InvalidInput`.
     /// On receiving EOT, performs the handshake and returns 0.
-    /// Otherwise, verifies the packet number, its complement, and checksum.
+    /// Otherwise, verifies the packet number, its complement, and checksum
+    /// (or CRC-16 in `Mode::Crc`).
+    /// If the sender retransmits the previous packet because our ACK was
+    /// lost, the packet is acknowledged again but discarded without touching
+    /// `buf`, and `AlreadyExists` is returned.
     pub fn read_packet(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         // Ensure buffer is large enough.
         if buf.len() < 128 {
-            return ioerr!(UnexpectedEof, "buffer too small for packet");
+            return ioerr!(InvalidInput, "buffer must be at least 128 bytes");
         }
         // Read header byte.
         let first = self.read_byte(true)?;
+        self.read_packet_after(first, buf)
+    }
+
+    /// Reads the remainder of a packet whose header byte `first` has already
+    /// been read.
+    fn read_packet_after(&mut self, first: u8, buf: &mut [u8]) -> io::Result<usize> {
         if first == EOT {
             // EOT handshake.
             self.write_byte(NAK)?;
@@ -218,50 +776,68 @@ impl<T: io::Read + io::Write> Xmodem<T> {
             self.write_byte(ACK)?;
             return Ok(0);
         }
-        // If header is not SOH, read one extra byte to decide the error kind.
-        if first != SOH {
+        // If header is not SOH or STX, read one extra byte to decide the error kind.
+        if first != SOH && first != STX {
             let second = self.read_byte(false)?;
             self.write_byte(CAN)?;
             if second == CAN {
                 return ioerr!(ConnectionAborted, "received CAN");
             } else {
-                return ioerr!(InvalidData, "expected SOH or EOT");
+                return ioerr!(InvalidData, "expected SOH, STX or EOT");
             }
         }
-        // Read packet number and its complement.
-        let pkt_num = self.read_byte(true)?;
-        let pkt_num_comp = self.read_byte(true)?;
-        if pkt_num != self.packet || pkt_num_comp != (255 - self.packet) {
+        let size = if first == STX { 1024 } else { 128 };
+        if buf.len() < size {
+            self.write_byte(CAN)?;
+            return ioerr!(InvalidInput, "buffer must be at least 1024 bytes for a 1K packet");
+        }
+        // Read packet number and its complement. Either may legitimately be
+        // CAN (packet 24 and packet 231), so neither aborts on it.
+        let pkt_num = self.read_byte(false)?;
+        let pkt_num_comp = self.read_byte(false)?;
+        let duplicate = pkt_num == self.packet.wrapping_sub(1);
+        if (pkt_num != self.packet && !duplicate) || pkt_num_comp != !pkt_num {
             self.write_byte(CAN)?;
             return ioerr!(InvalidData, "invalid packet number");
         }
-        // Read 128 bytes of packet data.
-        self.inner.read_exact(buf)?;
-        let checksum = get_checksum(buf);
-        let transmitted = self.read_byte(false)?;
-        if checksum != transmitted {
+        // Read the packet data, discarding that of a duplicate.
+        let mut scratch = [0u8; 1024];
+        let buf = if duplicate { &mut scratch[..size] } else { &mut buf[..size] };
+        self.read_exact(buf)?;
+        let valid = match self.mode {
+            Mode::Checksum => get_checksum(buf) == self.read_byte(false)?,
+            Mode::Crc => {
+                let mut crc = [0u8; 2];
+                self.read_exact(&mut crc)?;
+                get_crc16(buf) == u16::from_be_bytes(crc)
+            }
+        };
+        if !valid {
             self.write_byte(NAK)?;
             return ioerr!(Interrupted, "checksum mismatch");
         }
         // Packet received correctly: send ACK, update packet number, and report progress.
         self.write_byte(ACK)?;
+        if duplicate {
+            return ioerr!(AlreadyExists, "duplicate packet");
+        }
         self.packet = self.packet.wrapping_add(1);
         (self.progress)(Progress::Packet(self.packet));
-        Ok(128)
+        Ok(size)
     }
 
     /// Sends (uploads) a single packet to the inner stream.
     /// If buf is empty, performs the EOT handshake.
-    /// Otherwise, sends SOH, packet number, its complement, 128-byte data, and checksum,
-    /// then waits for the receiver's response.
+    /// Otherwise, sends SOH, packet number, its complement, 128-byte data, and checksum
+    /// (or CRC-16 in `Mode::Crc`), then waits for the receiver's response.
+    /// A 1024-byte buf is sent as an XMODEM-1K packet with an STX header.
     pub fn write_packet(&mut self, buf: &[u8]) -> io::Result<usize> {
         if buf.is_empty() {
             // EOT handshake:
             if !self.started {
                 // If the transmission hasn’t started yet,
                 // first consume the initial NAK from the receiver.
-                self.expect_byte(NAK, "initial NAK")?;
-                self.started = true;
+                self.wait_for_receiver()?;
             }
             self.write_byte(EOT)?;
             self.expect_byte(NAK, "NAK after EOT")?;
@@ -270,16 +846,19 @@ impl<T: io::Read + io::Write> Xmodem<T> {
             return Ok(0);
         } else {
             // Data packet transmission (unchanged) …
-            self.write_byte(SOH)?;
+            self.write_byte(if buf.len() == 1024 { STX } else { SOH })?;
             self.write_byte(self.packet)?;
-            self.write_byte(255 - self.packet)?;
+            self.write_byte(!self.packet)?;
             self.inner.write_all(buf)?;
-            let checksum = get_checksum(buf);
-            self.write_byte(checksum)?;
+            match self.mode {
+                Mode::Checksum => self.write_byte(get_checksum(buf))?,
+                Mode::Crc => self.inner.write_all(&get_crc16(buf).to_be_bytes())?,
+            }
 
             // Wait for receiver response.
             let response = self.read_byte(true)?;
             if response == NAK {
+                (self.progress)(Progress::Retransmit { packet: self.packet });
                 return ioerr!(Interrupted, "checksum mismatch, retransmit packet");
             }
             if response != ACK {
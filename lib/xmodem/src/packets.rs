@@ -0,0 +1,81 @@
+use shim::io;
+
+use crate::{Progress, Xmodem};
+
+/// An iterator over the 128-byte blocks of a transfer being received.
+///
+/// Each call to `next` yields the next block of the transfer, reading a new
+/// packet from the sender when the previous one has been consumed; a 1K
+/// packet is yielded as eight blocks. Iteration ends with `None` after the
+/// EOT handshake or right after an error has been yielded.
+///
+/// Returned by [`Xmodem::packets()`].
+pub struct Packets<T> {
+    xmodem: Xmodem<T>,
+    buf: [u8; 1024],
+    pos: usize,
+    len: usize,
+    started: bool,
+    done: bool,
+}
+
+impl<T: io::Read + io::Write> Packets<T> {
+    pub(crate) fn new(xmodem: Xmodem<T>) -> Packets<T> {
+        Packets {
+            xmodem,
+            buf: [0; 1024],
+            pos: 0,
+            len: 0,
+            started: false,
+            done: false,
+        }
+    }
+
+    /// Receives the next packet into the buffer, signaling readiness to the
+    /// sender first if this is the first packet.
+    fn fill(&mut self) -> io::Result<usize> {
+        let header = if self.started {
+            None
+        } else {
+            self.started = true;
+            Some(self.xmodem.start_receive()?)
+        };
+
+        self.xmodem.receive_packet(header, &mut self.buf)
+    }
+}
+
+impl<T: io::Read + io::Write> Iterator for Packets<T> {
+    type Item = io::Result<[u8; 128]>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        if self.pos == self.len {
+            match self.fill() {
+                Ok(0) => {
+                    self.done = true;
+                    return None;
+                }
+                Ok(n) => {
+                    self.pos = 0;
+                    self.len = n;
+                    self.xmodem.transferred += n;
+                    let transferred = self.xmodem.transferred;
+                    (self.xmodem.progress)(Progress::Bytes { transferred });
+                }
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+
+        let mut block = [0u8; 128];
+        block.copy_from_slice(&self.buf[self.pos..self.pos + 128]);
+        self.pos += 128;
+        Some(Ok(block))
+    }
+}
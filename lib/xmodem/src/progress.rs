@@ -1,17 +1,36 @@
+#[cfg(not(feature = "no_std"))]
+use shim::io;
+
 /// Enum representing how much progress has been made transmitting/receiving.
 ///
 /// A value of this type is passed in to the progress callback supplied to
 /// methods like [`Xmodem::transmit_with_progress()`],
 /// [`Xmodem::receive_with_progress()`], and [`Xmodem::new_with_progress()`]. It
 /// is intended to be used by progress indicators or for debugging purposes.
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Progress {
-    /// Waiting for receiver to send NAK.
+    /// Waiting for receiver to send NAK. Never reported; the transmitter
+    /// reports `WaitingHandshake` instead.
+    #[deprecated(note = "use `Progress::WaitingHandshake`")]
     Waiting,
+    /// The handshake hasn't completed yet. Reported by the transmitter while
+    /// it waits for the receiver's initial `NAK` or `C`, and by the receiver
+    /// each time it sends its initial `NAK` again because the sender didn't
+    /// answer.
+    WaitingHandshake,
     /// Download/upload has started.
     Started,
     /// Packet `.0` was transmitted/received.
     Packet(u8),
+    /// A packet was transmitted/received, bringing the number of data bytes
+    /// transferred so far to `transferred`.
+    Bytes { transferred: usize },
+    /// The receiver rejected packet `packet`, which will be retransmitted.
+    Retransmit { packet: u8 },
+    /// The transfer finished successfully. `packets` data packets were
+    /// transferred, and `bytes` data bytes were sent or written out by the
+    /// receiver. This is the last update of a transfer.
+    Completed { packets: usize, bytes: usize },
     NAK,
     Unknown,
 }
@@ -21,3 +40,66 @@ pub type ProgressFn = fn(Progress);
 
 /// Noop progress callback.
 pub fn noop(_: Progress) {  }
+
+/// Textual progress indicator drawn to a writer from `Progress` updates.
+///
+/// Because [`ProgressFn`] is a plain function pointer, the bar can't be used as
+/// a callback itself; instead, pass each update the callback receives to
+/// [`ProgressBar::update()`]. When the total number of packets is known, a bar
+/// like `[########    ]  66%` is redrawn for each packet. Otherwise, a spinner
+/// and the number of bytes transferred so far are redrawn after each packet.
+/// Each drawing starts with a carriage return to overwrite the previous one.
+#[cfg(not(feature = "no_std"))]
+pub struct ProgressBar<W> {
+    out: W,
+    total: Option<usize>,
+    packets: usize,
+}
+
+#[cfg(not(feature = "no_std"))]
+impl<W: io::Write> ProgressBar<W> {
+    /// Width of the bar in characters, excluding the brackets.
+    const WIDTH: usize = 20;
+
+    /// Characters cycled through by the spinner.
+    const SPINNER: &'static [u8] = b"|/-\\";
+
+    /// Returns a progress bar for a transfer of `total_packets` packets, if
+    /// known, that draws to `out`.
+    pub fn new(total_packets: Option<usize>, out: W) -> ProgressBar<W> {
+        ProgressBar { out, total: total_packets, packets: 0 }
+    }
+
+    /// Records `progress` and redraws the indicator if it changed.
+    pub fn update(&mut self, progress: Progress) -> io::Result<()> {
+        match (progress, self.total) {
+            (Progress::Packet(_), Some(total)) => {
+                self.packets += 1;
+                let done = core::cmp::min(self.packets, total);
+                let (filled, percent) = match total {
+                    0 => (Self::WIDTH, 100),
+                    _ => (done * Self::WIDTH / total, done * 100 / total),
+                };
+
+                self.out.write_all(b"\r[")?;
+                for i in 0..Self::WIDTH {
+                    self.out.write_all(if i < filled { b"#" } else { b" " })?;
+                }
+                write!(self.out, "] {:>3}%", percent)?;
+            }
+            (Progress::Packet(_), None) => self.packets += 1,
+            (Progress::Bytes { transferred }, None) => {
+                let spinner = Self::SPINNER[self.packets % Self::SPINNER.len()];
+                write!(self.out, "\r{} {} bytes", spinner as char, transferred)?;
+            }
+            _ => return Ok(()),
+        }
+
+        self.out.flush()
+    }
+
+    /// Consumes the progress bar, returning the writer it draws to.
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
@@ -1,7 +1,11 @@
 use shim::io;
 
 pub trait ReadExt: io::Read {
-    fn read_max(&mut self, mut buf: &mut [u8]) -> io::Result<usize> {
+    /// Reads until `buf` is full or a read returns `0`, retrying reads that
+    /// are interrupted. Returns the number of bytes read, which is less than
+    /// `buf.len()` only if the end of the stream was reached. Unlike
+    /// `read_exact`, reaching the end of the stream early isn't an error.
+    fn read_exact_or_eof(&mut self, mut buf: &mut [u8]) -> io::Result<usize> {
         let start_len = buf.len();
         while !buf.is_empty() {
             match self.read(buf) {
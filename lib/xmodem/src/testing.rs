@@ -0,0 +1,97 @@
+//! Helpers for testing code that speaks XMODEM against a real peer.
+//!
+//! Enabled by the `testing` feature; requires `std`.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+
+use shim::io;
+
+use crate::Xmodem;
+
+/// One direction of a pipe: a queue of bytes and whether its writer is gone.
+#[derive(Default)]
+struct Half {
+    state: Mutex<(VecDeque<u8>, bool)>,
+    ready: Condvar,
+}
+
+/// One end of an in-process, bidirectional byte pipe.
+///
+/// Bytes written to one end are read from the other. Reads block until at
+/// least one byte is available and return `Ok(0)` once the other end has
+/// been dropped and every byte has been read.
+pub struct Pipe {
+    rx: Arc<Half>,
+    tx: Arc<Half>,
+}
+
+/// Returns both ends of a new pipe.
+pub fn pipe() -> (Pipe, Pipe) {
+    let (a, b) = (Arc::new(Half::default()), Arc::new(Half::default()));
+    (Pipe { rx: a.clone(), tx: b.clone() }, Pipe { rx: b, tx: a })
+}
+
+impl io::Read for Pipe {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut state = self.rx.state.lock().unwrap();
+        while state.0.is_empty() && !state.1 {
+            state = self.rx.ready.wait(state).unwrap();
+        }
+
+        let n = buf.len().min(state.0.len());
+        for (slot, byte) in buf.iter_mut().zip(state.0.drain(..n)) {
+            *slot = byte;
+        }
+        Ok(n)
+    }
+}
+
+impl io::Write for Pipe {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.tx.state.lock().unwrap().0.extend(buf);
+        self.tx.ready.notify_all();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for Pipe {
+    fn drop(&mut self) {
+        self.tx.state.lock().unwrap().1 = true;
+        self.tx.ready.notify_all();
+    }
+}
+
+/// Transmits `data` to a receiver over a pipe, running each half on its own
+/// thread, and returns the bytes received without the final padding.
+pub fn transfer(data: &[u8]) -> io::Result<Vec<u8>> {
+    let (tx, rx) = pipe();
+    let input = data.to_vec();
+    let len = input.len();
+    let transmitter = thread::spawn(move || Xmodem::transmit(&input[..], tx));
+    let receiver = thread::spawn(move || {
+        let mut output = vec![];
+        Xmodem::receive_with_len(rx, &mut output, Some(len)).map(|_| output)
+    });
+
+    let sent = transmitter.join().expect("transmitter panicked")?;
+    let output = receiver.join().expect("receiver panicked")?;
+    assert_eq!(sent, len, "transmitter reported a short transfer");
+    Ok(output)
+}
+
+/// Transfers `data` with [`transfer`] and asserts that the receiver got
+/// exactly what was sent.
+///
+/// # Panics
+///
+/// Panics if the transfer fails or the received bytes differ from `data`.
+pub fn assert_round_trip(data: &[u8]) {
+    let output = transfer(data).expect("transfer failed");
+    assert_eq!(output, data, "received bytes differ from those sent");
+}
@@ -83,10 +83,10 @@ fn test_expect_byte() {
 }
 
 #[test]
-fn test_expect_byte_or_cancel() {
+fn test_expect_byte_in_place() {
     let mut buffer = vec![2, 0];
     let b = Xmodem::new(Cursor::new(buffer.as_mut_slice()))
-        .expect_byte_or_cancel(2, "it's a 2")
+        .expect_byte(2, "it's a 2")
         .expect("got a 2");
 
     assert_eq!(b, 2);
@@ -111,21 +111,88 @@ fn test_unexpected_can() {
 fn test_cancel_on_unexpected() {
     let mut buffer = vec![CAN, 0];
     let e = Xmodem::new(Cursor::new(buffer.as_mut_slice()))
-        .expect_byte_or_cancel(SOH, "want SOH")
+        .expect_byte(SOH, "want SOH")
         .expect_err("have CAN");
 
     assert_eq!(e.kind(), io::ErrorKind::ConnectionAborted);
-    assert_eq!(buffer[1], CAN);
+    assert_eq!(buffer[1], 0);
 
     let mut buffer = vec![0, 0];
     let e = Xmodem::new(Cursor::new(buffer.as_mut_slice()))
-        .expect_byte_or_cancel(SOH, "want SOH")
+        .expect_byte(SOH, "want SOH")
         .expect_err("have 0");
 
     assert_eq!(e.kind(), io::ErrorKind::InvalidData);
     assert_eq!(buffer[1], CAN);
 }
 
+#[test]
+fn test_expect_can_reply() {
+    // A CAN isn't answered in kind, since the peer has already given up.
+    let mut stream = mock(vec![CAN]);
+    let e = Xmodem::new(&mut stream).expect_byte(ACK, "ACK").expect_err("cancelled");
+    assert_eq!(e.kind(), io::ErrorKind::ConnectionAborted);
+    assert!(stream.written.is_empty());
+
+    // A wrong byte is answered with CAN.
+    let mut stream = mock(vec![0x42]);
+    let e = Xmodem::new(&mut stream).expect_byte(ACK, "ACK").expect_err("wrong byte");
+    assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+    assert_eq!(&stream.written[..], &[CAN]);
+}
+
+#[test]
+fn test_eot_handshake_errors() {
+    let mut packet = [0u8; 128];
+
+    // The receiver's second EOT.
+    let mut stream = mock(vec![EOT, CAN]);
+    let e = Xmodem::new(&mut stream).read_packet(&mut packet).expect_err("cancelled");
+    assert_eq!(e.kind(), io::ErrorKind::ConnectionAborted);
+    assert_eq!(&stream.written[..], &[NAK]);
+
+    let mut stream = mock(vec![EOT, 0x42]);
+    let e = Xmodem::new(&mut stream).read_packet(&mut packet).expect_err("wrong byte");
+    assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+    assert_eq!(e.to_string(), "expected second EOT, received 0x42");
+    assert_eq!(&stream.written[..], &[NAK, CAN]);
+
+    // The sender's NAK after the first EOT.
+    let mut stream = mock(vec![CAN]);
+    let e = Xmodem::new(&mut stream).without_handshake().write_packet(&[]).expect_err("cancelled");
+    assert_eq!(e.kind(), io::ErrorKind::ConnectionAborted);
+    assert_eq!(&stream.written[..], &[EOT]);
+
+    let mut stream = mock(vec![0x42]);
+    let e = Xmodem::new(&mut stream).without_handshake().write_packet(&[]).expect_err("wrong byte");
+    assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+    assert_eq!(e.to_string(), "expected NAK after EOT, received 0x42");
+    assert_eq!(&stream.written[..], &[EOT, CAN]);
+
+    // The sender's ACK after the second EOT.
+    let mut stream = mock(vec![NAK, CAN]);
+    let e = Xmodem::new(&mut stream).without_handshake().write_packet(&[]).expect_err("cancelled");
+    assert_eq!(e.kind(), io::ErrorKind::ConnectionAborted);
+    assert_eq!(&stream.written[..], &[EOT, EOT]);
+
+    let mut stream = mock(vec![NAK, 0x42]);
+    let e = Xmodem::new(&mut stream).without_handshake().write_packet(&[]).expect_err("wrong byte");
+    assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+    assert_eq!(e.to_string(), "expected ACK after second EOT, received 0x42");
+    assert_eq!(&stream.written[..], &[EOT, EOT, CAN]);
+}
+
+#[test]
+fn test_packet_response_errors() {
+    let mut xmodem = Xmodem::new(mock(vec![CAN])).without_handshake();
+    let e = xmodem.write_packet(&[0; 128]).expect_err("cancelled");
+    assert_eq!(e.kind(), io::ErrorKind::ConnectionAborted);
+
+    let mut xmodem = Xmodem::new(mock(vec![0x42])).without_handshake();
+    let e = xmodem.write_packet(&[0; 128]).expect_err("wrong byte");
+    assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+}
+
 #[test]
 fn test_can_in_packet_and_checksum() {
     let mut input = [0u8; 256];
@@ -195,8 +262,8 @@ fn test_small_packet_eof_error() {
     let mut xmodem = Xmodem::new(Cursor::new(vec![NAK, NAK, NAK]));
 
     let mut buffer = [1, 2, 3];
-    let e = xmodem.read_packet(&mut buffer[..]).expect_err("read EOF");
-    assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof);
+    let e = xmodem.read_packet(&mut buffer[..]).expect_err("small buffer");
+    assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
 
     let e = xmodem.write_packet(&buffer).expect_err("write EOF");
     assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof);
@@ -227,3 +294,809 @@ fn test_eot() {
 
     assert_eq!(&buffer[..], &[NAK, EOT, NAK, EOT, ACK]);
 }
+
+#[test]
+fn test_crc16() {
+    assert_eq!(get_crc16(b""), 0);
+    assert_eq!(get_crc16(b"123456789"), 0x31C3);
+}
+
+#[test]
+fn test_checksum_selftest() {
+    // Evaluated at compile time, as a boot selftest could be.
+    const CRC: u16 = get_crc16(b"123456789");
+    const CHECKSUM: u8 = get_checksum(b"123456789");
+    assert_eq!(CRC, 0x31C3);
+    assert_eq!(CHECKSUM, 0xDD);
+
+    assert_eq!(get_crc16(b"A"), 0x58E5);
+    assert_eq!(get_crc16(&[0; 128]), 0);
+    assert_eq!(get_checksum(&[]), 0);
+    assert_eq!(get_checksum(&[0xFF, 0x02]), 0x01);
+
+    assert!(verify_crc16(b"123456789", 0x31C3));
+    assert!(!verify_crc16(b"123456788", 0x31C3));
+    assert!(verify_checksum(b"123456789", 0xDD));
+    assert!(!verify_checksum(b"123456789", 0xDE));
+}
+
+fn round_trip(input: Vec<u8>, mode: Mode) -> (usize, Vec<u8>, Mode) {
+    let len = input.len();
+    let (tx, rx) = pipe();
+    let tx_thread = std::thread::spawn(move || Xmodem::transmit(&input[..], rx));
+    let rx_thread = std::thread::spawn(move || {
+        let mut output = vec![];
+        let mut receiver = Xmodem::new(tx).with_mode(mode);
+        receiver.recv(&mut output).map(|_| (output, receiver.mode()))
+    });
+
+    let sent = tx_thread.join().expect("tx join okay").expect("tx okay");
+    let (output, mode) = rx_thread.join().expect("rx join okay").expect("rx okay");
+    assert!(output.len() >= len);
+    (sent, output, mode)
+}
+
+#[test]
+fn test_round_trip_both_modes() {
+    let input: Vec<u8> = (0..300usize).map(|i| (i * 7) as u8).collect();
+    for &mode in &[Mode::Checksum, Mode::Crc] {
+        let (sent, output, negotiated) = round_trip(input.clone(), mode);
+        assert_eq!(sent, 300);
+        assert_eq!(negotiated, mode);
+        assert_eq!(&output[..300], &input[..]);
+        assert!(output[300..].iter().all(|b| *b == 0));
+    }
+}
+
+#[test]
+fn test_raw_crc_transmission() {
+    let mut input = [0u8; 128];
+    (0..128usize).for_each(|i| input[i] = i as u8);
+    let mut output = [0u8; 128];
+
+    let (mut tx, mut rx) = pipe();
+    let tx_thread = std::thread::spawn(move || {
+        Xmodem::transmit(&input[..], &mut rx).expect("transmit okay");
+        rx.2
+    });
+
+    let rx_thread = std::thread::spawn(move || {
+        Xmodem::receive_crc(&mut tx, &mut output[..]).expect("receive okay");
+        tx.2
+    });
+
+    let rx_buf = tx_thread.join().expect("tx join okay");
+    let tx_buf = rx_thread.join().expect("rx join okay");
+
+    assert_eq!(&rx_buf[0..3], &[SOH, 1, 255 - 1]);
+    assert_eq!(&rx_buf[3..131], &input[..]);
+    assert_eq!(&rx_buf[131..133], &get_crc16(&input).to_be_bytes());
+    assert_eq!(&rx_buf[133..], &[EOT, EOT]);
+    assert_eq!(&tx_buf, &[CRC, ACK, NAK, ACK]);
+}
+
+/// A stream whose first `timeouts` reads time out before yielding `data`.
+/// Everything written to it is captured in `written`.
+struct Mock {
+    timeouts: usize,
+    data: Cursor<Vec<u8>>,
+    written: Vec<u8>,
+}
+
+impl io::Read for Mock {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.timeouts > 0 {
+            self.timeouts -= 1;
+            return ioerr!(TimedOut, "no reply");
+        }
+        self.data.read(buf)
+    }
+}
+
+fn mock(data: Vec<u8>) -> Mock {
+    Mock { timeouts: 0, data: Cursor::new(data), written: vec![] }
+}
+
+impl io::Write for Mock {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.written.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_crc_falls_back_to_checksum() {
+    let mut data = vec![SOH, 1, 254];
+    data.extend_from_slice(&[7u8; 128]);
+    data.push(get_checksum(&[7u8; 128]));
+    data.extend_from_slice(&[EOT, EOT]);
+
+    let mut stream = Mock { timeouts: 3, ..mock(data) };
+    let mut output = vec![];
+    let mut receiver = Xmodem::new(&mut stream).with_mode(Mode::Crc);
+    assert_eq!(receiver.recv(&mut output).expect("receive okay"), 128);
+    assert_eq!(receiver.mode(), Mode::Checksum);
+
+    assert_eq!(&output[..], &[7u8; 128][..]);
+    assert_eq!(&stream.written[..], &[CRC, CRC, CRC, NAK, ACK, NAK, ACK]);
+}
+
+#[test]
+fn test_1k_blocks_with_short_tail() {
+    let input: Vec<u8> = (0..2500usize).map(|i| (i % 251) as u8).collect();
+    let expected = input.clone();
+
+    let (mut tx, mut rx) = pipe();
+    let tx_thread = std::thread::spawn(move || {
+        let mut transmitter = Xmodem::new(&mut rx).with_block_size(1024);
+        assert_eq!(transmitter.block_size(), 1024);
+        let sent = transmitter.send(&input[..]).expect("transmit okay");
+        (sent, rx.2)
+    });
+    let rx_thread = std::thread::spawn(move || {
+        let mut output = vec![];
+        Xmodem::receive(&mut tx, &mut output).map(|n| (n, output))
+    });
+
+    let (sent, wire) = tx_thread.join().expect("tx join okay");
+    let (received, output) = rx_thread.join().expect("rx join okay").expect("rx okay");
+    assert_eq!(sent, 2500);
+    assert_eq!(received, 2 * 1024 + 4 * 128);
+    assert_eq!(&output[..2500], &expected[..]);
+    assert!(output[2500..].iter().all(|b| *b == 0));
+
+    // Two 1K packets followed by four 128-byte packets.
+    let mut headers = vec![];
+    let mut i = 0;
+    while wire[i] != EOT {
+        headers.push((wire[i], wire[i + 1]));
+        i += 3 + if wire[i] == STX { 1024 } else { 128 } + 1;
+    }
+    assert_eq!(headers, vec![(STX, 1), (STX, 2), (SOH, 3), (SOH, 4), (SOH, 5), (SOH, 6)]);
+}
+
+#[test]
+fn test_read_1k_packet() {
+    let data = [9u8; 1024];
+    let mut stream = vec![STX, 1, 254];
+    stream.extend_from_slice(&data);
+    stream.push(get_checksum(&data));
+    stream.push(0);
+
+    let mut buffer = [0u8; 1024];
+    let n = Xmodem::new(Cursor::new(stream.as_mut_slice()))
+        .read_packet(&mut buffer)
+        .expect("read 1K packet");
+    assert_eq!(n, 1024);
+    assert_eq!(&buffer[..], &data[..]);
+    assert_eq!(*stream.last().unwrap(), ACK);
+
+    let mut small = [0u8; 128];
+    let e = Xmodem::new(Cursor::new(vec![STX, 1, 254]))
+        .read_packet(&mut small)
+        .expect_err("1K packet in small buffer");
+    assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn test_max_retries() {
+    let mut stream = mock(vec![NAK, NAK]);
+    let e = Xmodem::new(&mut stream)
+        .with_max_retries(1)
+        .send(&[7u8; 10][..])
+        .expect_err("one attempt");
+    assert_eq!(e.kind(), io::ErrorKind::BrokenPipe);
+    assert_eq!(e.to_string(), "bad transmit (packet 1)");
+    assert_eq!(stream.written.len(), 3 + 128 + 1);
+
+    // A packet with a bad checksum, sent twice.
+    let mut data = vec![];
+    for _ in 0..2 {
+        data.extend_from_slice(&[SOH, 1, 254]);
+        data.extend_from_slice(&[1u8; 128]);
+        data.push(0);
+    }
+    let mut stream = mock(data);
+    let e = Xmodem::new(&mut stream)
+        .with_max_retries(2)
+        .recv(vec![])
+        .expect_err("two attempts");
+    assert_eq!(e.kind(), io::ErrorKind::BrokenPipe);
+    assert_eq!(e.to_string(), "bad receive (packet 1)");
+    assert_eq!(&stream.written[..], &[NAK, NAK, NAK]);
+}
+
+fn ticks() -> u64 {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    static TICKS: AtomicU64 = AtomicU64::new(0);
+    TICKS.fetch_add(1, Ordering::Relaxed)
+}
+
+/// A stream that never has a byte ready.
+struct Silent;
+
+impl io::Read for Silent {
+    fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+        ioerr!(WouldBlock, "nothing yet")
+    }
+}
+
+impl io::Write for Silent {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_read_timeout() {
+    let timeout = Timeout { ticks: 100, now: ticks };
+    let e = Xmodem::new(Silent)
+        .with_timeout(Some(timeout))
+        .read_byte(false)
+        .expect_err("times out");
+    assert_eq!(e.kind(), io::ErrorKind::TimedOut);
+
+    // The receiver's NAK arrives, but no response to either packet does.
+    let mut stream = mock(vec![NAK]);
+    let e = Xmodem::new(&mut stream)
+        .with_timeout(Some(timeout))
+        .with_max_retries(2)
+        .send(&[7u8; 10][..])
+        .expect_err("retries exhausted");
+    assert_eq!(e.kind(), io::ErrorKind::BrokenPipe);
+    assert_eq!(stream.written.len(), 2 * (3 + 128 + 1));
+}
+
+#[test]
+fn test_expect_byte_errors() {
+    let e = Xmodem::new(mock(vec![0x42]))
+        .expect_byte(ACK, "ACK")
+        .expect_err("wrong byte");
+    assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+    assert_eq!(e.to_string(), "expected ACK, received 0x42");
+
+    let e = Xmodem::new(mock(vec![CAN]))
+        .expect_byte(ACK, "ACK")
+        .expect_err("cancelled");
+    assert_eq!(e.kind(), io::ErrorKind::ConnectionAborted);
+
+    let timeout = Timeout { ticks: 100, now: ticks };
+    let e = Xmodem::new(Silent)
+        .with_timeout(Some(timeout))
+        .expect_byte(ACK, "ACK")
+        .expect_err("times out");
+    assert_eq!(e.kind(), io::ErrorKind::TimedOut);
+}
+
+/// Returns the bytes of a checksum-mode packet numbered `num` holding `data`.
+fn packet_bytes(num: u8, data: &[u8; 128]) -> Vec<u8> {
+    let mut bytes = vec![SOH, num, !num];
+    bytes.extend_from_slice(data);
+    bytes.push(get_checksum(data));
+    bytes
+}
+
+thread_local! {
+    static EVENTS: std::cell::RefCell<Vec<Progress>> = const { std::cell::RefCell::new(vec![]) };
+}
+
+fn record(progress: Progress) {
+    EVENTS.with(|events| events.borrow_mut().push(progress));
+}
+
+fn recorded() -> Vec<Progress> {
+    EVENTS.with(|events| events.replace(vec![]))
+}
+
+#[test]
+fn test_byte_progress() {
+    let mut data = packet_bytes(1, &[1; 128]);
+    data.extend(packet_bytes(2, &[2; 128]));
+    data.extend_from_slice(&[EOT, EOT]);
+
+    let stream = Mock { timeouts: 3, ..mock(data) };
+    Xmodem::receive_with_progress(stream, vec![], record).expect("receive okay");
+    assert_eq!(recorded(), vec![
+        Progress::Started,
+        Progress::Packet(2),
+        Progress::Bytes { transferred: 128 },
+        Progress::Packet(3),
+        Progress::Bytes { transferred: 256 },
+        Progress::Completed { packets: 2, bytes: 256 },
+    ]);
+
+    Xmodem::transmit_with_progress(&[7u8; 10][..], mock(vec![NAK, ACK, NAK, ACK]), record)
+        .expect("transmit okay");
+    assert_eq!(recorded(), vec![
+        Progress::WaitingHandshake,
+        Progress::Started,
+        Progress::Packet(2),
+        Progress::Bytes { transferred: 10 },
+        Progress::Completed { packets: 1, bytes: 10 },
+    ]);
+}
+
+#[test]
+fn test_retransmit_progress() {
+    Xmodem::transmit_with_progress(&[7u8; 10][..], mock(vec![NAK, NAK, ACK, NAK, ACK]), record)
+        .expect("transmit okay");
+
+    let events = recorded();
+    assert_eq!(events[0], Progress::WaitingHandshake);
+    let retransmits: Vec<_> = events.iter()
+        .filter(|event| matches!(event, Progress::Retransmit { .. }))
+        .collect();
+    assert_eq!(retransmits, vec![&Progress::Retransmit { packet: 1 }]);
+    assert!(events.contains(&Progress::Packet(2)));
+}
+
+#[test]
+fn test_cancel() {
+    let mut stream = mock(vec![]);
+    Xmodem::new(&mut stream).cancel().expect("cancelled");
+    assert_eq!(&stream.written[..], &[CAN, CAN]);
+
+    let mut packet = [0u8; 128];
+    let e = Xmodem::new(Cursor::new(vec![CAN, CAN]))
+        .read_packet(&mut packet)
+        .expect_err("aborted by peer");
+    assert_eq!(e.kind(), io::ErrorKind::ConnectionAborted);
+}
+
+static CANCEL: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+fn cancel_after_first_packet(progress: Progress) {
+    if let Progress::Packet(_) = progress {
+        CANCEL.store(true, std::sync::atomic::Ordering::Relaxed);
+    }
+}
+
+#[test]
+fn test_cancel_from_progress() {
+    let mut stream = mock(vec![NAK, ACK, ACK]);
+    let e = Xmodem::new_with_progress(&mut stream, cancel_after_first_packet)
+        .with_cancel_flag(&CANCEL)
+        .send(&[7u8; 256][..])
+        .expect_err("cancelled");
+    assert_eq!(e.kind(), io::ErrorKind::ConnectionAborted);
+    assert_eq!(stream.written.len(), 3 + 128 + 1 + 2);
+    assert!(stream.written.ends_with(&[CAN, CAN]));
+}
+
+#[test]
+fn test_receive_with_len() {
+    let input: Vec<u8> = (1..=200).collect();
+    let expected = input.clone();
+
+    let (tx, rx) = pipe();
+    let tx_thread = std::thread::spawn(move || Xmodem::transmit(&input[..], rx));
+    let rx_thread = std::thread::spawn(move || {
+        let mut output = vec![];
+        Xmodem::receive_with_len(tx, &mut output, Some(200)).map(|n| (n, output))
+    });
+
+    assert_eq!(tx_thread.join().expect("tx join okay").expect("tx okay"), 200);
+    let (n, output) = rx_thread.join().expect("rx join okay").expect("rx okay");
+    assert_eq!(n, 200);
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn test_packets_iterator() {
+    let mut data = vec![];
+    for i in 1..=3 {
+        data.extend(packet_bytes(i, &[i; 128]));
+    }
+    data.extend_from_slice(&[EOT, EOT]);
+
+    let mut stream = mock(data.clone());
+    let blocks: Vec<[u8; 128]> = Xmodem::new(&mut stream)
+        .packets()
+        .collect::<io::Result<_>>()
+        .expect("all packets");
+    assert_eq!(blocks.len(), 3);
+    for (i, block) in blocks.iter().enumerate() {
+        assert!(block.iter().all(|b| *b == i as u8 + 1));
+    }
+    assert_eq!(&stream.written[..], &[NAK, ACK, ACK, ACK, NAK, ACK]);
+
+    // Dropping the iterator mid-stream is fine.
+    let mut packets = Xmodem::new(mock(data)).packets();
+    assert!(packets.next().expect("a block").is_ok());
+    drop(packets);
+
+    // An error ends iteration.
+    let mut packets = Xmodem::new(mock(vec![0, 0])).packets();
+    let e = packets.next().expect("an error").expect_err("bad header");
+    assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+    assert!(packets.next().is_none());
+}
+
+#[test]
+fn test_state_after_failed_transmit() {
+    let mut stream = mock(vec![NAK, ACK, ACK, 0]);
+    let mut transmitter = Xmodem::new(&mut stream);
+    let e = transmitter.send(&[7u8; 300][..]).expect_err("bad response");
+    assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+    assert_eq!(transmitter.packet_number(), 3);
+    assert_eq!(transmitter.bytes_transferred(), 256);
+}
+
+#[test]
+fn test_loopback() {
+    testing::assert_round_trip(&[]);
+    testing::assert_round_trip(b"hello, world");
+    let input: Vec<u8> = (0..1000usize).map(|i| (i % 256) as u8).collect();
+    testing::assert_round_trip(&input);
+}
+
+#[test]
+fn test_transmit_detailed() {
+    let mut stream = mock(vec![NAK, ACK, ACK, NAK, ACK]);
+    let stats = Xmodem::transmit_detailed(&[7u8; 130][..], &mut stream).expect("transmit okay");
+    assert_eq!(stats, TransmitStats { data_bytes: 130, padded_bytes: 126, packets: 2 });
+    assert!(stream.written[137..263].iter().all(|&byte| byte == 0));
+
+    let mut stream = mock(vec![NAK, ACK, NAK, ACK]);
+    let stats = Xmodem::transmit_detailed(&[7u8; 128][..], &mut stream).expect("transmit okay");
+    assert_eq!(stats, TransmitStats { data_bytes: 128, padded_bytes: 0, packets: 1 });
+}
+
+#[test]
+fn test_transmit_without_handshake() {
+    use std::io::Read;
+
+    let input: Vec<u8> = (0..300usize).map(|i| i as u8).collect();
+    let (mut tx, rx) = testing::pipe();
+    let receiver = std::thread::spawn(move || {
+        let mut output = vec![];
+        Xmodem::receive(rx, &mut output).map(|_| output)
+    });
+
+    // Consume the receiver's handshake ourselves, as a synced link would.
+    let mut nak = [0u8; 1];
+    tx.read_exact(&mut nak).expect("handshake");
+    assert_eq!(nak[0], NAK);
+
+    let sent = Xmodem::transmit_without_handshake(&input[..], tx).expect("transmit okay");
+    assert_eq!(sent, input.len());
+    let output = receiver.join().expect("receiver thread").expect("receive okay");
+    assert_eq!(&output[..input.len()], &input[..]);
+    assert_eq!(output.len(), 384);
+}
+
+#[test]
+fn test_duplicates_dont_use_up_retries() {
+    // A sender that keeps losing ACKs resends packet 1 more times than the
+    // receiver would retry a bad packet.
+    let mut data = vec![];
+    for _ in 0..5 {
+        data.extend(packet_bytes(1, &[1; 128]));
+    }
+    data.extend(packet_bytes(2, &[2; 128]));
+    data.extend_from_slice(&[EOT, EOT]);
+
+    let mut output = vec![];
+    let received = Xmodem::new(mock(data)).with_max_retries(2).recv(&mut output);
+    assert_eq!(received.expect("receive okay"), 256);
+    assert_eq!(&output[..128], &[1; 128][..]);
+    assert_eq!(&output[128..], &[2; 128][..]);
+}
+
+#[test]
+fn test_packet_number_wraps() {
+    // 260 packets: numbers run 1..=255, then wrap to 0..=4.
+    let input: Vec<u8> = (0..260 * 128usize).map(|i| (i / 128) as u8).collect();
+    testing::assert_round_trip(&input);
+
+    let mut stream = mock(packet_bytes(0, &[1; 128]));
+    let mut xmodem = Xmodem::new(&mut stream);
+    xmodem.packet = 0;
+    let mut packet = [0u8; 128];
+    assert_eq!(xmodem.read_packet(&mut packet).expect("packet 0 accepted"), 128);
+    assert_eq!(xmodem.packet_number(), 1);
+}
+
+#[test]
+fn test_duplicate_packet() {
+    let mut data = packet_bytes(1, &[1; 128]);
+    data.extend(packet_bytes(1, &[1; 128]));
+    data.extend(packet_bytes(2, &[2; 128]));
+    data.extend_from_slice(&[EOT, EOT]);
+
+    let mut stream = mock(data);
+    let mut output = vec![];
+    assert_eq!(Xmodem::receive(&mut stream, &mut output).expect("receive okay"), 256);
+    assert_eq!(&output[..128], &[1; 128][..]);
+    assert_eq!(&output[128..], &[2; 128][..]);
+    assert_eq!(&stream.written[..], &[NAK, ACK, ACK, ACK, NAK, ACK]);
+
+    let mut buffer = [0u8; 128];
+    let mut xmodem = Xmodem::new(mock(packet_bytes(1, &[1; 128])));
+    xmodem.packet = 2;
+    let e = xmodem.read_packet(&mut buffer).expect_err("duplicate");
+    assert_eq!(e.kind(), io::ErrorKind::AlreadyExists);
+    assert_eq!(buffer, [0; 128]);
+    assert_eq!(xmodem.packet_number(), 2);
+}
+
+/// A reader returning one byte of `.0` per call, with every other call
+/// interrupted.
+struct Trickle(Cursor<Vec<u8>>, bool);
+
+impl io::Read for Trickle {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.1 = !self.1;
+        if self.1 {
+            return ioerr!(Interrupted, "interrupted");
+        }
+
+        let len = std::cmp::min(buf.len(), 1);
+        self.0.read(&mut buf[..len])
+    }
+}
+
+#[test]
+fn test_read_exact_or_eof() {
+    let mut trickle = Trickle(Cursor::new((0..10).collect()), false);
+    let mut buf = [0u8; 4];
+    assert_eq!(trickle.read_exact_or_eof(&mut buf).expect("read"), 4);
+    assert_eq!(buf, [0, 1, 2, 3]);
+
+    let mut buf = [0u8; 8];
+    assert_eq!(trickle.read_exact_or_eof(&mut buf).expect("read"), 6);
+    assert_eq!(&buf[..6], &[4, 5, 6, 7, 8, 9]);
+    assert_eq!(trickle.read_exact_or_eof(&mut buf).expect("read"), 0);
+}
+
+#[test]
+fn test_transmit_trickled_data() {
+    let input: Vec<u8> = (0..200u8).collect();
+    let mut trickle = Trickle(Cursor::new(input.clone()), false);
+    let mut stream = mock(vec![NAK, ACK, ACK, NAK, ACK]);
+    let sent = Xmodem::transmit(&mut trickle, &mut stream).expect("transmit okay");
+    assert_eq!(sent, 200);
+
+    // Both packets are full despite the source producing one byte per read.
+    let written = &stream.written;
+    assert_eq!(written.len(), 2 * (3 + 128 + 1) + 2);
+    assert_eq!(&written[3..131], &input[..128]);
+    assert_eq!(&written[135..207], &input[128..]);
+    assert!(written[207..263].iter().all(|&byte| byte == 0));
+}
+
+#[test]
+fn test_into_inner() {
+    let mut xmodem = Xmodem::new(mock(vec![NAK, ACK, NAK, ACK]));
+    assert_eq!(xmodem.get_ref().written.len(), 0);
+    xmodem.send(&[7u8; 128][..]).expect("send okay");
+
+    let written = xmodem.get_ref().written.len();
+    assert_eq!(written, 3 + 128 + 1 + 2);
+    io::Write::write_all(xmodem.get_mut(), &[CAN]).expect("write");
+
+    let stream = xmodem.into_inner();
+    assert_eq!(stream.written.len(), written + 1);
+    assert_eq!(stream.written.last(), Some(&CAN));
+}
+
+#[test]
+fn test_progress_bar() {
+    let mut bar = ProgressBar::new(Some(5), vec![]);
+    for packet in 1..=2 {
+        bar.update(Progress::Packet(packet)).expect("draw");
+        bar.update(Progress::Bytes { transferred: packet as usize * 128 }).expect("draw");
+    }
+    bar.update(Progress::Started).expect("draw");
+
+    let output = String::from_utf8(bar.into_inner()).expect("ascii");
+    assert_eq!(output, "\r[####                ]  20%\r[########            ]  40%");
+
+    let mut bar = ProgressBar::new(Some(2), vec![]);
+    for packet in 1..=3 {
+        bar.update(Progress::Packet(packet)).expect("draw");
+    }
+    let output = String::from_utf8(bar.into_inner()).expect("ascii");
+    assert!(output.ends_with("\r[####################] 100%"));
+}
+
+#[test]
+fn test_progress_spinner() {
+    let mut bar = ProgressBar::new(None, vec![]);
+    for packet in 1..=3 {
+        bar.update(Progress::Packet(packet)).expect("draw");
+        bar.update(Progress::Bytes { transferred: packet as usize * 128 }).expect("draw");
+    }
+
+    let output = String::from_utf8(bar.into_inner()).expect("ascii");
+    assert_eq!(output, "\r/ 128 bytes\r- 256 bytes\r\\ 384 bytes");
+}
+
+#[test]
+fn test_read_packet_buffer_size() {
+    let mut buffer = [0u8; 64];
+    let mut xmodem = Xmodem::new(mock(packet_bytes(1, &[1; 128])));
+    let e = xmodem.read_packet(&mut buffer).expect_err("small buffer");
+    assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+    assert_eq!(e.to_string(), "buffer must be at least 128 bytes");
+
+    // Nothing was consumed, so the packet can still be read.
+    assert!(xmodem.get_ref().written.is_empty());
+    let mut buffer = [0u8; 129];
+    assert_eq!(xmodem.read_packet(&mut buffer).expect("read packet"), 128);
+    assert_eq!(&buffer[..128], &[1; 128][..]);
+}
+
+#[test]
+fn test_reset_back_to_back_receives() {
+    let (first, second): (Vec<u8>, Vec<u8>) = ((0..200u8).collect(), (100..150u8).collect());
+    let (mut tx, rx) = testing::pipe();
+    let receiver = std::thread::spawn(move || {
+        let mut xmodem = Xmodem::new(rx);
+        let mut outputs = (vec![], vec![]);
+        xmodem.recv(&mut outputs.0)?;
+        xmodem.reset();
+        assert_eq!((xmodem.packet_number(), xmodem.bytes_transferred()), (1, 0));
+        xmodem.recv(&mut outputs.1)?;
+        Ok::<_, io::Error>(outputs)
+    });
+
+    Xmodem::transmit(&first[..], &mut tx).expect("first transmit okay");
+    Xmodem::transmit(&second[..], &mut tx).expect("second transmit okay");
+    let outputs = receiver.join().expect("receiver thread").expect("receive okay");
+    assert_eq!(&outputs.0[..200], &first[..]);
+    assert_eq!(outputs.0.len(), 256);
+    assert_eq!(&outputs.1[..50], &second[..]);
+    assert_eq!(outputs.1.len(), 128);
+}
+
+#[test]
+fn test_transmit_count_with_retransmits() {
+    let input: Vec<u8> = (0..200u8).collect();
+    let mut stream = mock(vec![NAK, ACK, NAK, NAK, ACK, NAK, ACK]);
+    let stats = Xmodem::transmit_detailed(&input[..], &mut stream).expect("transmit okay");
+    assert_eq!(stats, TransmitStats { data_bytes: 200, padded_bytes: 56, packets: 2 });
+
+    // Packet 2 went out three times, each time with the same data.
+    let packet_len = 3 + 128 + 1;
+    assert_eq!(stream.written.len(), 4 * packet_len + 2);
+    for attempt in 1..4 {
+        let packet = &stream.written[attempt * packet_len..(attempt + 1) * packet_len];
+        assert_eq!(&packet[..3], &[SOH, 2, !2]);
+        assert_eq!(&packet[3..75], &input[128..]);
+    }
+
+    let mut stream = mock(vec![NAK, ACK, NAK, NAK, ACK, NAK, ACK]);
+    assert_eq!(Xmodem::transmit(&input[..], &mut stream).expect("transmit okay"), 200);
+}
+
+#[test]
+fn test_set_packet_number() {
+    let mut xmodem = Xmodem::new(mock(packet_bytes(5, &[5; 128])));
+    xmodem.set_packet_number(5);
+    assert_eq!(xmodem.packet_number(), 5);
+    let mut packet = [0u8; 128];
+    assert_eq!(xmodem.read_packet(&mut packet).expect("packet 5 accepted"), 128);
+    assert_eq!(packet, [5; 128]);
+    assert_eq!(xmodem.packet_number(), 6);
+
+    let mut xmodem = Xmodem::new(mock(packet_bytes(1, &[1; 128])));
+    xmodem.set_packet_number(5);
+    let e = xmodem.read_packet(&mut packet).expect_err("packet 1 rejected");
+    assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+
+    let mut stream = mock(vec![ACK]);
+    let mut xmodem = Xmodem::new(&mut stream).without_handshake();
+    xmodem.set_packet_number(5);
+    xmodem.write_packet(&[7; 128]).expect("write packet");
+    assert_eq!(&stream.written[..3], &[SOH, 5, !5]);
+}
+
+#[test]
+fn test_completed_progress() {
+    let completed = |events: Vec<Progress>| -> Vec<Progress> {
+        events.into_iter().filter(|event| matches!(event, Progress::Completed { .. })).collect()
+    };
+
+    let mut data = packet_bytes(1, &[1; 128]);
+    data.extend(packet_bytes(2, &[2; 128]));
+    data.extend(packet_bytes(3, &[3; 128]));
+    data.extend_from_slice(&[EOT, EOT]);
+    let stream = Mock { timeouts: 3, ..mock(data) };
+    Xmodem::receive_with_progress(stream, vec![], record).expect("receive okay");
+    let events = recorded();
+    assert_eq!(events.last(), Some(&Progress::Completed { packets: 3, bytes: 384 }));
+    assert_eq!(completed(events).len(), 1);
+
+    let input = [9u8; 300];
+    Xmodem::transmit_with_progress(&input[..], mock(vec![NAK, ACK, ACK, ACK, NAK, ACK]), record)
+        .expect("transmit okay");
+    let events = recorded();
+    assert_eq!(events.last(), Some(&Progress::Completed { packets: 3, bytes: 300 }));
+    assert_eq!(completed(events).len(), 1);
+}
+
+#[test]
+fn test_receive_with_tee() {
+    let input: Vec<u8> = (0..300).map(|i| i as u8).collect();
+    let (mut tx, rx) = pipe();
+    let receiver = std::thread::spawn(move || {
+        let (mut output, mut log) = (vec![], vec![]);
+        let n = Xmodem::receive_with_tee(rx, &mut output, &mut log, progress::noop)?;
+        Ok::<_, io::Error>((n, output, log))
+    });
+
+    Xmodem::transmit(&input[..], &mut tx).expect("transmit okay");
+    let (n, output, log) = receiver.join().expect("receiver thread").expect("receive okay");
+    assert_eq!(n, 384);
+    assert_eq!(&output[..300], &input[..]);
+    assert_eq!(output, log);
+}
+
+#[test]
+fn test_receiver_handshake_retries() {
+    let mut stream = Mock { timeouts: usize::MAX, ..mock(vec![]) };
+    let e = Xmodem::new_with_progress(&mut stream, record)
+        .with_handshake_attempts(4)
+        .recv(vec![])
+        .expect_err("sender never starts");
+    assert_eq!(e.kind(), io::ErrorKind::TimedOut);
+    assert_eq!(&stream.written[..], &[NAK; 4]);
+
+    let events = recorded();
+    assert_eq!(events[0], Progress::Started);
+    assert_eq!(events[1..], [Progress::WaitingHandshake; 3]);
+
+    // A sender appearing after two timeouts is received from as usual.
+    let mut data = packet_bytes(1, &[1; 128]);
+    data.extend_from_slice(&[EOT, EOT]);
+    let mut stream = Mock { timeouts: 2, ..mock(data) };
+    let mut output = vec![];
+    Xmodem::new(&mut stream).recv(&mut output).expect("receive okay");
+    assert_eq!(output, [1; 128]);
+    assert_eq!(&stream.written[..], &[NAK, NAK, NAK, ACK, NAK, ACK]);
+}
+
+#[test]
+fn test_receive_negotiates_crc() {
+    let input = [5u8; 200];
+    let (mut tx, mut rx) = pipe();
+    let tx_thread = std::thread::spawn(move || {
+        Xmodem::transmit(&input[..], &mut rx).expect("transmit okay");
+        rx.2
+    });
+    let rx_thread = std::thread::spawn(move || {
+        let mut output = vec![];
+        Xmodem::receive_with_progress(&mut tx, &mut output, progress::noop).expect("receive okay");
+        (output, tx.2)
+    });
+
+    let wire = tx_thread.join().expect("tx join okay");
+    let (output, replies) = rx_thread.join().expect("rx join okay");
+    assert_eq!(&output[..200], &input[..]);
+    assert_eq!(replies[0], CRC);
+
+    // Each packet carries a two-byte CRC.
+    assert_eq!(&wire[131..133], &get_crc16(&input[..128]).to_be_bytes());
+    assert_eq!(wire[133], SOH);
+}
+
+#[test]
+fn test_receive_falls_back_for_checksum_sender() {
+    let mut data = packet_bytes(1, &[3; 128]);
+    data.extend_from_slice(&[EOT, EOT]);
+
+    let mut stream = Mock { timeouts: 3, ..mock(data) };
+    let mut output = vec![];
+    let received = Xmodem::receive_with_progress(&mut stream, &mut output, progress::noop);
+    assert_eq!(received.expect("receive okay"), 128);
+    assert_eq!(&output[..], &[3; 128][..]);
+    assert_eq!(&stream.written[..], &[CRC, CRC, CRC, NAK, ACK, NAK, ACK]);
+}